@@ -0,0 +1,324 @@
+use std::{cmp::Ordering, io::Stdout};
+
+use time::{format_description::well_known::Rfc3339, OffsetDateTime, UtcOffset};
+use tui::{
+    backend::CrosstermBackend,
+    layout::{Constraint, Direction, Layout, Rect},
+    style::{Color, Style},
+    symbols::Marker,
+    text::Span,
+    widgets::{Axis, BarChart, Block, Chart, Dataset, GraphType},
+    Frame,
+};
+
+use crate::{
+    overview::ResultRow,
+    query_syntax::{command_is, commands, tokenize, Token, TokenKind},
+};
+
+const COLORS: [Color; 6] =
+    [Color::Cyan, Color::Yellow, Color::Green, Color::Magenta, Color::Red, Color::Blue];
+
+/// How results are shown, a chart is only drawn for results that can be charted.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum ResultsView {
+    Line,
+    Bar,
+    Table,
+}
+
+impl ResultsView {
+    pub(crate) fn next(self) -> Self {
+        match self {
+            ResultsView::Line => ResultsView::Bar,
+            ResultsView::Bar => ResultsView::Table,
+            ResultsView::Table => ResultsView::Line,
+        }
+    }
+}
+
+/// Results of a `stats ... by bin()` query as one series per aggregate.
+#[derive(Debug, PartialEq)]
+pub(crate) struct ChartData {
+    pub(crate) series: Vec<Series>,
+    /// First and last bin as unix timestamps.
+    x_bounds: [f64; 2],
+    y_bounds: [f64; 2],
+}
+
+#[derive(Debug, PartialEq)]
+pub(crate) struct Series {
+    pub(crate) name: String,
+    /// Bins as unix timestamps with their values, oldest first.
+    pub(crate) points: Vec<(f64, f64)>,
+}
+
+/// Insights timestamps look like `2021-11-01 10:00:00.000` and are in UTC.
+pub(crate) fn parse_timestamp(value: &str) -> Option<OffsetDateTime> {
+    OffsetDateTime::parse(&format!("{}Z", value.replacen(' ', "T", 1)), &Rfc3339).ok()
+}
+
+fn all_values(rows: &[ResultRow], column: &str, check: impl Fn(&str) -> bool) -> bool {
+    rows.iter().all(|row| row.get(column).map(&check).unwrap_or(false))
+}
+
+/// The column of the `bin()` a query groups its last `stats` by, named after the expression
+/// unless it is renamed with `as`. `None` when the query doesn't group by a `bin()`.
+pub(crate) fn bin_column(query: &str) -> Option<String> {
+    let stats = commands(query).into_iter().rev().find(|c| command_is(c, "stats"))?;
+    let tokens = tokenize(&stats);
+    let by = tokens
+        .iter()
+        .position(|t| t.kind == TokenKind::Word && t.text.eq_ignore_ascii_case("by"))?;
+    let mut groups: Vec<Vec<Token>> = vec![vec![]];
+    let mut depth = 0;
+    for token in &tokens[by + 1..] {
+        match token.text {
+            "(" => depth += 1,
+            ")" => depth -= 1,
+            "," if depth == 0 => {
+                groups.push(vec![]);
+                continue;
+            }
+            _ => {}
+        }
+        if token.kind != TokenKind::Whitespace {
+            groups.last_mut().unwrap().push(*token);
+        }
+    }
+    groups.into_iter().find_map(|group| {
+        if !group.first()?.text.eq_ignore_ascii_case("bin") || group.get(1)?.text != "(" {
+            return None;
+        }
+        match group
+            .iter()
+            .position(|t| t.kind == TokenKind::Word && t.text.eq_ignore_ascii_case("as"))
+        {
+            Some(alias) => group.get(alias + 1).map(|t| t.text.trim_matches('`').to_string()),
+            None => Some(group.iter().map(|t| t.text).collect()),
+        }
+    })
+}
+
+/// Charts `rows` over the time bins in the column `bin` with a series for every column with
+/// numbers, `None` when the bins or numbers are missing. Any other columns are what the query
+/// grouped by besides the bins, every value of them gets its own series.
+pub(crate) fn chart_data(rows: &[ResultRow], columns: &[String], bin: &str) -> Option<ChartData> {
+    if rows.is_empty() {
+        return None;
+    }
+    let bin = columns
+        .iter()
+        .find(|c| *c == bin && all_values(rows, c, |v| parse_timestamp(v).is_some()))?;
+    let (values, groups): (Vec<&String>, Vec<&String>) = columns
+        .iter()
+        .filter(|c| *c != bin)
+        .partition(|c| all_values(rows, c, |v| v.parse::<f64>().is_ok()));
+    if values.is_empty() {
+        return None;
+    }
+
+    let mut series: Vec<Series> = vec![];
+    for row in rows {
+        let x = parse_timestamp(row.get(bin)?)?.unix_timestamp() as f64;
+        let group: Vec<String> =
+            groups.iter().map(|g| format!("{}={}", g, row.get(g).unwrap_or(""))).collect();
+        for value in &values {
+            let name = if group.is_empty() {
+                value.to_string()
+            } else {
+                format!("{} {}", value, group.join(" "))
+            };
+            let y = row.get(value)?.parse::<f64>().ok()?;
+            match series.iter_mut().find(|s| s.name == name) {
+                Some(series) => series.points.push((x, y)),
+                None => series.push(Series { name, points: vec![(x, y)] }),
+            }
+        }
+    }
+    for series in &mut series {
+        series.points.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(Ordering::Equal));
+    }
+
+    let points = || series.iter().flat_map(|s| s.points.iter());
+    let x_min = points().map(|p| p.0).fold(f64::INFINITY, f64::min);
+    let x_max = points().map(|p| p.0).fold(f64::NEG_INFINITY, f64::max);
+    let y_min = points().map(|p| p.1).fold(0.0, f64::min);
+    let y_max = points().map(|p| p.1).fold(0.0, f64::max);
+    Some(ChartData {
+        series,
+        x_bounds: [x_min, x_max.max(x_min + 1.0)],
+        y_bounds: [y_min, y_max.max(y_min + 1.0)],
+    })
+}
+
+/// Formats a bin in `offset`, with the date only when the chart spans more than a day.
+pub(crate) fn format_time(timestamp: f64, span: f64, offset: UtcOffset) -> String {
+    let time = match OffsetDateTime::from_unix_timestamp(timestamp as i64) {
+        Ok(time) => time.to_offset(offset),
+        Err(_) => return timestamp.to_string(),
+    };
+    if span > 86_400.0 {
+        format!(
+            "{:02}-{:02} {:02}:{:02}",
+            time.month() as u8,
+            time.day(),
+            time.hour(),
+            time.minute()
+        )
+    } else if span < 120.0 {
+        format!("{:02}:{:02}:{:02}", time.hour(), time.minute(), time.second())
+    } else {
+        format!("{:02}:{:02}", time.hour(), time.minute())
+    }
+}
+
+fn format_value(value: f64) -> String {
+    if value.fract() == 0.0 {
+        format!("{}", value)
+    } else {
+        format!("{:.2}", value)
+    }
+}
+
+/// Draws `data` as a line chart, or as one bar chart per series, with times shown in `offset`.
+pub(crate) fn draw(
+    frame: &mut Frame<CrosstermBackend<Stdout>>,
+    area: Rect,
+    block: Block,
+    data: &ChartData,
+    view: ResultsView,
+    offset: UtcOffset,
+) {
+    match view {
+        ResultsView::Bar => draw_bars(frame, area, block, data, offset),
+        _ => draw_lines(frame, area, block, data, offset),
+    }
+}
+
+fn draw_lines(
+    frame: &mut Frame<CrosstermBackend<Stdout>>,
+    area: Rect,
+    block: Block,
+    data: &ChartData,
+    offset: UtcOffset,
+) {
+    let [x_min, x_max] = data.x_bounds;
+    let [y_min, y_max] = data.y_bounds;
+    let span = x_max - x_min;
+    let datasets = data
+        .series
+        .iter()
+        .enumerate()
+        .map(|(i, series)| {
+            Dataset::default()
+                .name(series.name.as_str())
+                .marker(Marker::Braille)
+                .graph_type(GraphType::Line)
+                .style(Style::default().fg(COLORS[i % COLORS.len()]))
+                .data(&series.points)
+        })
+        .collect();
+    let x_labels = [x_min, x_min + span / 2.0, x_max]
+        .iter()
+        .map(|x| Span::raw(format_time(*x, span, offset)))
+        .collect();
+    let y_labels =
+        [y_min, (y_min + y_max) / 2.0, y_max].iter().map(|y| Span::raw(format_value(*y))).collect();
+    let chart = Chart::new(datasets)
+        .block(block)
+        .hidden_legend_constraints((Constraint::Ratio(1, 2), Constraint::Ratio(1, 2)))
+        .x_axis(Axis::default().bounds(data.x_bounds).labels(x_labels))
+        .y_axis(Axis::default().bounds(data.y_bounds).labels(y_labels));
+    frame.render_widget(chart, area);
+}
+
+fn draw_bars(
+    frame: &mut Frame<CrosstermBackend<Stdout>>,
+    area: Rect,
+    block: Block,
+    data: &ChartData,
+    offset: UtcOffset,
+) {
+    let inner = block.inner(area);
+    frame.render_widget(block, area);
+    let span = data.x_bounds[1] - data.x_bounds[0];
+    let areas = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints(
+            data.series
+                .iter()
+                .map(|_| Constraint::Ratio(1, data.series.len() as u32))
+                .collect::<Vec<_>>(),
+        )
+        .split(inner);
+    for (i, (series, area)) in data.series.iter().zip(areas).enumerate() {
+        let labels: Vec<String> =
+            series.points.iter().map(|(x, _)| format_time(*x, span, offset)).collect();
+        let bars: Vec<(&str, u64)> = labels
+            .iter()
+            .zip(&series.points)
+            .map(|(label, (_, y))| (label.as_str(), y.max(0.0).round() as u64))
+            .collect();
+        let bar_width = (area.width as usize / bars.len().max(1)).clamp(2, 10) as u16 - 1;
+        let chart = BarChart::default()
+            .block(Block::default().title(series.name.as_str()))
+            .data(&bars)
+            .bar_width(bar_width)
+            .bar_gap(1)
+            .bar_style(Style::default().fg(COLORS[i % COLORS.len()]))
+            .value_style(Style::default().fg(Color::Black).bg(COLORS[i % COLORS.len()]));
+        frame.render_widget(chart, area);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(fields: &[(&str, &str)]) -> ResultRow {
+        ResultRow { fields: fields.iter().map(|(f, v)| (f.to_string(), v.to_string())).collect() }
+    }
+
+    #[test]
+    fn bin_column_is_found_in_the_by_clause() {
+        let cases = [
+            ("stats count(*) by bin(5m)", Some("bin(5m)")),
+            ("stats count(*) BY bin( 5m )", Some("bin(5m)")),
+            ("stats avg(@duration), count(*) by @logStream, bin(1h)", Some("bin(1h)")),
+            ("stats count(*) by bin(1m) as minute, @logStream", Some("minute")),
+            ("stats count(*) by datefloor(@timestamp, 1m)", None),
+            ("stats count(*) by @logStream", None),
+            ("fields bin(5m) | filter @message like /by bin(5m)/", None),
+            ("stats count(*) by bin(1m) | stats max(c) by bin(1h)", Some("bin(1h)")),
+        ];
+        for (query, column) in cases {
+            assert_eq!(bin_column(query).as_deref(), column, "{}", query);
+        }
+    }
+
+    #[test]
+    fn other_time_columns_are_not_taken_for_the_bins() {
+        let query = "stats latest(@timestamp) as last, count(*) by bin(1m) as t";
+        let rows = vec![
+            row(&[
+                ("last", "2021-11-01 09:00:00.000"),
+                ("t", "2021-11-01 10:01:00.000"),
+                ("count(*)", "3"),
+            ]),
+            row(&[
+                ("last", "2021-11-01 09:00:00.000"),
+                ("t", "2021-11-01 10:00:00.000"),
+                ("count(*)", "7"),
+            ]),
+        ];
+        let columns = vec!["last".to_string(), "t".to_string(), "count(*)".to_string()];
+
+        let data = chart_data(&rows, &columns, &bin_column(query).unwrap()).unwrap();
+
+        let start = 1_635_760_800.0;
+        assert_eq!(data.series.len(), 1);
+        assert_eq!(data.series[0].points, vec![(start, 7.0), (start + 60.0, 3.0)]);
+        assert_eq!(chart_data(&rows, &columns, "bin(1m)"), None);
+    }
+}
@@ -0,0 +1,358 @@
+use std::{
+    collections::HashSet,
+    error::Error,
+    fs,
+    io::{self, stdout, BufWriter, Read, Write},
+};
+
+use crate::{
+    config::Config,
+    cwl::{
+        self,
+        backend::{LogsBackend, QueryStatus},
+        error::BackendError,
+        retry::{retry_message, with_retry},
+    },
+    output::{self, OutputFormat},
+    overview::ResultRow,
+    query_lint::lint,
+    time_select::TimeSelector,
+};
+
+pub(crate) const USAGE: &str = "\
+Usage: rcwi [query OPTIONS]
+       rcwi -h | --help
+
+Without a command the interactive TUI is started.
+
+query  Run a Cloudwatch Insights query and print the results to stdout
+    -g, --log-group <NAME>   Log group to query, can be given multiple times
+                             (default log_groups from the config)
+    -s, --start <TIME>       Start of the time range (default 1h or start from the config)
+    -e, --end <TIME>         End of the time range (default now or end from the config)
+    -q, --query <QUERY>      Query to run
+    -f, --file <PATH>        Read the query from a file, `-` for stdin
+    -o, --output <FORMAT>    json (JSON Lines), json-array, csv or table (default table),
+                             JSON Lines are printed as the rows arrive
+    -w, --write <PATH>       Write the results to a file instead of stdout, the format
+                             is picked from the extension unless --output is given";
+
+struct QueryArgs {
+    log_groups: Vec<String>,
+    start: String,
+    end: String,
+    query: String,
+    output: Option<OutputFormat>,
+    output_file: Option<String>,
+}
+
+impl QueryArgs {
+    /// Parses the arguments of the `query` command, `None` when help was asked for.
+    fn parse(args: &[String], config: &Config) -> Result<Option<Self>, Box<dyn Error>> {
+        let default_time = config.time_selector()?;
+        let mut query_args = QueryArgs {
+            log_groups: vec![],
+            start: default_time.selected_start_string,
+            end: default_time.selected_end_string,
+            query: config.query().to_string(),
+            output: None,
+            output_file: None,
+        };
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .cloned()
+                    .ok_or_else(|| format!("Missing value for {}\n\n{}", arg, USAGE))
+            };
+            match arg.as_str() {
+                "-g" | "--log-group" => query_args.log_groups.push(value()?),
+                "-s" | "--start" => query_args.start = value()?,
+                "-e" | "--end" => query_args.end = value()?,
+                "-q" | "--query" => query_args.query = value()?,
+                "-f" | "--file" => {
+                    let path = value()?;
+                    query_args.query = read_query(&path)
+                        .map_err(|err| format!("Could not read query from {}: {}", path, err))?;
+                }
+                "-o" | "--output" => query_args.output = Some(value()?.parse()?),
+                "-w" | "--write" => query_args.output_file = Some(value()?),
+                "-h" | "--help" => return Ok(None),
+                _ => return Err(format!("Unknown argument '{}'\n\n{}", arg, USAGE).into()),
+            }
+        }
+        if query_args.log_groups.is_empty() {
+            query_args.log_groups = config.log_groups.clone();
+        }
+        if query_args.log_groups.is_empty() {
+            return Err(format!("At least one log group is required\n\n{}", USAGE).into());
+        }
+        Ok(Some(query_args))
+    }
+}
+
+fn read_query(path: &str) -> io::Result<String> {
+    if path == "-" {
+        let mut query = String::new();
+        io::stdin().read_to_string(&mut query)?;
+        Ok(query)
+    } else {
+        fs::read_to_string(path)
+    }
+}
+
+/// Runs a single query without starting the TUI and prints the results to stdout.
+pub(crate) fn run_query(args: &[String], config: &Config) -> Result<(), Box<dyn Error>> {
+    let args = match QueryArgs::parse(args, config)? {
+        Some(args) => args,
+        None => {
+            println!("{}", USAGE);
+            return Ok(());
+        }
+    };
+    let basic_rt = tokio::runtime::Builder::new_current_thread().enable_all().build()?;
+    basic_rt.block_on(async {
+        let backend = cwl::aws_backend(config).await;
+        run(&args, config, &backend, &mut BufWriter::new(stdout())).await
+    })
+}
+
+/// Runs the query of `args` in `backend` and writes the results to `out`.
+///
+/// JSON Lines are written on every poll as rows with a new `@ptr` show up, other formats and
+/// rows without `@ptr`, like those of `stats`, are written once the query completes.
+async fn run(
+    args: &QueryArgs,
+    config: &Config,
+    backend: &dyn LogsBackend,
+    out: &mut dyn Write,
+) -> Result<(), Box<dyn Error>> {
+    lint(&args.query).map_err(|err| format!("Invalid query at {}", err))?;
+    let (start, end) = TimeSelector::from_strings(&args.start, &args.end)?.to_timestamps();
+
+    let policy = config.retry_policy();
+    let on_retry = |attempt, err: &BackendError, delay| {
+        eprintln!("{}", retry_message("Request", &policy, attempt, err, delay))
+    };
+    let streaming = args.output_file.is_none() && args.output == Some(OutputFormat::JsonLines);
+    let mut printed: HashSet<String> = HashSet::new();
+    let mut write_error = None;
+    let query_id = with_retry(&policy, on_retry, || {
+        backend.start_query(args.log_groups.clone(), args.query.clone(), start, end)
+    })
+    .await?;
+    let res = cwl::wait_for_results(
+        backend,
+        &query_id,
+        config.poll_interval(),
+        &policy,
+        on_retry,
+        |res| {
+            if !streaming {
+                return true;
+            }
+            let new_rows: Vec<ResultRow> = res
+                .results
+                .iter()
+                .filter(|row| {
+                    row.get("@ptr").map(|ptr| printed.insert(ptr.to_string())).unwrap_or(false)
+                })
+                .cloned()
+                .collect();
+            match output::write_rows(&mut *out, &new_rows, OutputFormat::JsonLines) {
+                Ok(()) => true,
+                Err(err) => {
+                    write_error = Some(err);
+                    false
+                }
+            }
+        },
+    )
+    .await?;
+    if !res.status.is_done() {
+        backend.stop_query(&query_id).await?;
+    }
+    if let Some(err) = write_error {
+        return Err(err.into());
+    }
+    if res.status != QueryStatus::Complete {
+        return Err(format!("Query ended with status {:?}", res.status).into());
+    }
+
+    if streaming {
+        let rest: Vec<ResultRow> = res
+            .results
+            .into_iter()
+            .filter(|row| row.get("@ptr").map(|ptr| !printed.contains(ptr)).unwrap_or(true))
+            .collect();
+        output::write_rows(out, &rest, OutputFormat::JsonLines)?;
+        return Ok(());
+    }
+    match &args.output_file {
+        Some(path) => {
+            let format = args
+                .output
+                .or_else(|| OutputFormat::from_path(path))
+                .ok_or("Unknown file extension, use .csv, .json or .ndjson or give --output")?;
+            output::write_file(path, &res.results, format)?;
+        }
+        None => {
+            output::write_rows(out, &res.results, args.output.unwrap_or(OutputFormat::Table))?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cwl::{backend::QueryResults, fake::FakeBackend};
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|a| a.to_string()).collect()
+    }
+
+    fn parse(a: &[&str], config: &str) -> Result<Option<QueryArgs>, String> {
+        QueryArgs::parse(&args(a), &Config::parse(config).unwrap()).map_err(|err| err.to_string())
+    }
+
+    fn row(fields: &[(&str, &str)]) -> ResultRow {
+        ResultRow { fields: fields.iter().map(|(f, v)| (f.to_string(), v.to_string())).collect() }
+    }
+
+    fn results(status: QueryStatus, rows: Vec<ResultRow>) -> QueryResults {
+        QueryResults { status, results: rows, statistics: None }
+    }
+
+    /// Runs the query against `backend`, returning what was written and the error, if any.
+    fn run_with(a: &[&str], backend: &FakeBackend) -> (String, Result<(), String>) {
+        let config = Config::parse("poll_interval_ms = 1\nretry_base_delay_ms = 1").unwrap();
+        let query_args = QueryArgs::parse(&args(a), &config).unwrap().unwrap();
+        let mut out = vec![];
+        let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+        let res = rt.block_on(run(&query_args, &config, backend, &mut out));
+        (String::from_utf8(out).unwrap(), res.map_err(|err| err.to_string()))
+    }
+
+    #[test]
+    fn flags_are_read() {
+        let query_args = parse(
+            &[
+                "-g",
+                "/a",
+                "--log-group",
+                "/b",
+                "-s",
+                "2h",
+                "--end",
+                "now",
+                "-q",
+                "fields x",
+                "-o",
+                "csv",
+                "-w",
+                "out.txt",
+            ],
+            "",
+        )
+        .unwrap()
+        .unwrap();
+
+        assert_eq!(query_args.log_groups, vec!["/a", "/b"]);
+        assert_eq!((query_args.start.as_str(), query_args.end.as_str()), ("2h", "now"));
+        assert_eq!(query_args.query, "fields x");
+        assert_eq!(query_args.output, Some(OutputFormat::Csv));
+        assert_eq!(query_args.output_file.as_deref(), Some("out.txt"));
+    }
+
+    #[test]
+    fn defaults_come_from_the_config() {
+        let query_args = parse(&[], "log_groups = [\"/a\"]").unwrap().unwrap();
+        assert_eq!((query_args.start.as_str(), query_args.end.as_str()), ("1h", "now"));
+        assert_eq!(query_args.log_groups, vec!["/a"]);
+        assert_eq!(query_args.output, None);
+
+        let config = "log_groups = [\"/a\"]\nstart = \"15m\"\nquery = \"fields @message\"";
+        let query_args = parse(&["-g", "/b"], config).unwrap().unwrap();
+        assert_eq!((query_args.start.as_str(), query_args.end.as_str()), ("15m", "now"));
+        assert_eq!(query_args.query, "fields @message");
+        assert_eq!(query_args.log_groups, vec!["/b"]);
+    }
+
+    #[test]
+    fn help_is_asked_for() {
+        assert!(parse(&["-g", "/a", "--help"], "").unwrap().is_none());
+        assert!(parse(&["-h"], "").unwrap().is_none());
+    }
+
+    #[test]
+    fn bad_arguments_are_rejected() {
+        let err = |a: &[&str]| parse(a, "").err().unwrap();
+
+        assert!(err(&["-g", "/a", "-q"]).starts_with("Missing value for -q\n\nUsage:"));
+        assert!(err(&["-g", "/a", "--colour"]).starts_with("Unknown argument '--colour'"));
+        assert!(err(&["-g", "/a", "-o", "xml"]).starts_with("Unknown output format 'xml'"));
+        assert!(err(&["-q", "fields x"]).starts_with("At least one log group is required"));
+    }
+
+    #[test]
+    fn results_are_printed_once_the_query_completes() {
+        let rows = vec![row(&[("@ptr", "p1"), ("level", "info"), ("@message", "a, b")])];
+        let backend = FakeBackend::default().with_query_results(vec![
+            results(QueryStatus::Running, vec![]),
+            results(QueryStatus::Complete, rows),
+        ]);
+
+        let (out, res) = run_with(&["-g", "/a", "-q", "fields level", "-o", "csv"], &backend);
+
+        assert_eq!(res, Ok(()));
+        assert_eq!(out, "level,@message\ninfo,\"a, b\"\n");
+        assert_eq!(*backend.started_queries.lock().unwrap(), vec!["fields level"]);
+    }
+
+    #[test]
+    fn json_lines_are_printed_as_rows_arrive() {
+        let first = row(&[("@ptr", "p1"), ("@message", "first")]);
+        let second = row(&[("@ptr", "p2"), ("@message", "second")]);
+        let backend = FakeBackend::default().with_query_results(vec![
+            results(QueryStatus::Running, vec![first.clone()]),
+            results(QueryStatus::Complete, vec![first, second]),
+        ]);
+
+        let (out, res) = run_with(&["-g", "/a", "-o", "json"], &backend);
+
+        assert_eq!(res, Ok(()));
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0], r#"{"@ptr":"p1","@message":"first"}"#);
+        assert_eq!(lines[1], r#"{"@ptr":"p2","@message":"second"}"#);
+    }
+
+    #[test]
+    fn failed_queries_are_errors() {
+        let backend = FakeBackend::default().with_query_results(vec![results(
+            QueryStatus::Failed,
+            vec![row(&[("@message", "partial")])],
+        )]);
+
+        let (out, res) = run_with(&["-g", "/a", "-o", "csv"], &backend);
+
+        assert_eq!(res, Err("Query ended with status Failed".to_string()));
+        assert_eq!(out, "");
+
+        // Without canned results polling the query fails.
+        let (out, res) = run_with(&["-g", "/a"], &FakeBackend::default());
+        assert_eq!(res, Err("the request failed (no canned query results)".to_string()));
+        assert_eq!(out, "");
+    }
+
+    #[test]
+    fn invalid_queries_are_not_started() {
+        let backend = FakeBackend::default();
+
+        let (_, res) = run_with(&["-g", "/a", "-q", "fields x | sort"], &backend);
+
+        assert!(res.unwrap_err().starts_with("Invalid query at "));
+        assert!(backend.started_queries.lock().unwrap().is_empty());
+    }
+}
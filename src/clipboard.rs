@@ -0,0 +1,15 @@
+use arboard::Clipboard;
+use log::error;
+
+use crate::status_bar::StatusMessage;
+
+/// Copies `content` to the system clipboard, returning the status to show for it.
+pub(crate) fn yank(content: String) -> StatusMessage {
+    let res = Clipboard::new().and_then(|mut cb| cb.set_text(content));
+    if let Err(err) = res {
+        error!("Clipboard action failed: {:?}", err);
+        StatusMessage::error("Clipboard action failed.")
+    } else {
+        StatusMessage::info("Yanked to clipboard.")
+    }
+}
@@ -0,0 +1,268 @@
+use std::{error::Error, fs, io, path::Path, time::Duration};
+
+use flexi_logger::LogSpecification;
+use serde::Deserialize;
+use time::UtcOffset;
+
+use crate::{cwl::retry::RetryPolicy, time_select::TimeSelector, DEFAULT_QUERY};
+
+const CONFIG_FILE: &str = "config.toml";
+const DEFAULT_POLL_INTERVAL_MS: u64 = 500;
+const DEFAULT_LOG_LEVEL: &str = "info";
+const DEFAULT_MAX_RETRIES: u32 = 5;
+const DEFAULT_RETRY_BASE_DELAY_MS: u64 = 250;
+const DEFAULT_RETRY_MAX_DELAY_MS: u64 = 10_000;
+const DEFAULT_SLICE_CONCURRENCY: usize = 4;
+
+/// User defaults read from `~/.rcwi/config.toml`, every key is optional.
+///
+/// ```toml
+/// region = "eu-west-1"
+/// profile = "prod"
+/// query = "fields @timestamp, @message | limit 20"
+/// start = "15m"
+/// end = "now"
+/// log_groups = ["/aws/lambda/my-function"]
+/// poll_interval_ms = 1000
+/// log_level = "debug"
+/// builtin_editor = true
+/// max_retries = 5
+/// retry_base_delay_ms = 250
+/// retry_max_delay_ms = 10000
+/// histogram = true
+/// timezone = "+02:00"
+/// time_slicing = true
+/// slice_concurrency = 4
+///
+/// [[targets]]
+/// region = "us-east-1"
+///
+/// [[targets]]
+/// region = "eu-west-1"
+/// profile = "other-account"
+/// ```
+#[derive(Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct Config {
+    pub(crate) region: Option<String>,
+    pub(crate) profile: Option<String>,
+    query: Option<String>,
+    start: Option<String>,
+    end: Option<String>,
+    pub(crate) log_groups: Vec<String>,
+    poll_interval_ms: Option<u64>,
+    log_level: Option<String>,
+    /// Enter on the query opens the editor inside the TUI instead of `$EDITOR`.
+    pub(crate) builtin_editor: bool,
+    /// Throttled requests are retried this many times, 0 turns retrying off.
+    max_retries: Option<u32>,
+    retry_base_delay_ms: Option<u64>,
+    retry_max_delay_ms: Option<u64>,
+    /// The histogram of matching events is shown above the results from the start.
+    pub(crate) histogram: bool,
+    /// Times in charts are shown with this offset from UTC, like `+02:00`.
+    timezone: Option<String>,
+    /// Queries hitting the row limit are run again in smaller time windows to get all rows.
+    time_slicing: bool,
+    /// How many time windows of a query run at once.
+    slice_concurrency: Option<usize>,
+    /// Queries are fanned out to all targets when any are given.
+    pub(crate) targets: Vec<QueryTarget>,
+}
+
+/// A region, and optionally a profile, a query is run in when fanning out.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct QueryTarget {
+    pub(crate) region: String,
+    #[serde(default)]
+    pub(crate) profile: Option<String>,
+}
+
+impl Config {
+    /// Reads the config from `dir`, a missing file means everything is left at its default.
+    ///
+    /// All values are validated up front so a typo is reported at startup instead of the
+    /// first time the value is used.
+    pub(crate) fn load(dir: &Path) -> Result<Self, Box<dyn Error>> {
+        let path = dir.join(CONFIG_FILE);
+        let content = match fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(err) => return Err(format!("Could not read {}: {}", path.display(), err).into()),
+        };
+        let config = Self::parse(&content)
+            .map_err(|err| format!("Invalid config file {}: {}", path.display(), err))?;
+        Ok(config)
+    }
+
+    pub(crate) fn parse(content: &str) -> Result<Self, Box<dyn Error>> {
+        let config: Config = toml::from_str(content)?;
+        if let Some(region) = &config.region {
+            if !is_region(region) {
+                return Err(
+                    format!("invalid region '{}', expected one like eu-west-1", region).into()
+                );
+            }
+        }
+        config.time_selector()?;
+        config.utc_offset()?;
+        LogSpecification::parse(config.log_level())
+            .map_err(|err| format!("invalid log_level '{}': {}", config.log_level(), err))?;
+        if config.poll_interval_ms == Some(0) {
+            return Err("poll_interval_ms must be greater than 0".into());
+        }
+        if config.slice_concurrency == Some(0) {
+            return Err("slice_concurrency must be greater than 0".into());
+        }
+        Ok(config)
+    }
+
+    pub(crate) fn query(&self) -> &str {
+        self.query.as_deref().unwrap_or(DEFAULT_QUERY)
+    }
+
+    /// The default time range, falling back to the built in default for a missing start or end.
+    pub(crate) fn time_selector(&self) -> Result<TimeSelector, String> {
+        let default = TimeSelector::default();
+        let start = self.start.as_deref().unwrap_or(&default.selected_start_string);
+        let end = self.end.as_deref().unwrap_or(&default.selected_end_string);
+        TimeSelector::from_strings(start, end).map_err(|err| {
+            format!("invalid time range start = '{}', end = '{}': {}", start, end, err)
+        })
+    }
+
+    pub(crate) fn poll_interval(&self) -> Duration {
+        Duration::from_millis(self.poll_interval_ms.unwrap_or(DEFAULT_POLL_INTERVAL_MS))
+    }
+
+    pub(crate) fn retry_policy(&self) -> RetryPolicy {
+        RetryPolicy {
+            max_retries: self.max_retries.unwrap_or(DEFAULT_MAX_RETRIES),
+            base_delay: Duration::from_millis(
+                self.retry_base_delay_ms.unwrap_or(DEFAULT_RETRY_BASE_DELAY_MS),
+            ),
+            max_delay: Duration::from_millis(
+                self.retry_max_delay_ms.unwrap_or(DEFAULT_RETRY_MAX_DELAY_MS),
+            ),
+        }
+    }
+
+    /// How many time windows of a query run at once, `None` when queries aren't split up.
+    pub(crate) fn time_slicing(&self) -> Option<usize> {
+        if self.time_slicing {
+            Some(self.slice_concurrency.unwrap_or(DEFAULT_SLICE_CONCURRENCY))
+        } else {
+            None
+        }
+    }
+
+    /// The offset of the configured timezone, UTC when there is none.
+    pub(crate) fn utc_offset(&self) -> Result<UtcOffset, String> {
+        let timezone = match self.timezone.as_deref() {
+            None | Some("UTC") | Some("Z") => return Ok(UtcOffset::UTC),
+            Some(timezone) => timezone,
+        };
+        let invalid = || format!("invalid timezone '{}', expected UTC or +HH:MM", timezone);
+        let (sign, rest) = match (timezone.strip_prefix('+'), timezone.strip_prefix('-')) {
+            (Some(rest), _) => (1, rest),
+            (_, Some(rest)) => (-1, rest),
+            _ => return Err(invalid()),
+        };
+        let (hours, minutes) = rest.split_once(':').ok_or_else(invalid)?;
+        let hours: u8 = hours.parse().map_err(|_| invalid())?;
+        let minutes: u8 = minutes.parse().map_err(|_| invalid())?;
+        if hours > 23 || minutes > 59 {
+            return Err(invalid());
+        }
+        UtcOffset::from_hms(sign * hours as i8, sign * minutes as i8, 0).map_err(|_| invalid())
+    }
+
+    pub(crate) fn log_level(&self) -> &str {
+        self.log_level.as_deref().unwrap_or(DEFAULT_LOG_LEVEL)
+    }
+
+    /// The config used to build the client for `target`, falling back to the main profile.
+    pub(crate) fn for_target(&self, target: &QueryTarget) -> Config {
+        Config {
+            profile: target.profile.clone().or_else(|| self.profile.clone()),
+            region: Some(target.region.clone()),
+            targets: vec![],
+            ..self.clone()
+        }
+    }
+}
+
+/// Whether `region` looks like an AWS region such as `eu-west-1` or `us-gov-east-1`, the
+/// regions themselves change too often to list them here.
+fn is_region(region: &str) -> bool {
+    let parts: Vec<&str> = region.split('-').collect();
+    parts.len() >= 3
+        && parts[..parts.len() - 1]
+            .iter()
+            .all(|p| !p.is_empty() && p.chars().all(|c| c.is_ascii_lowercase()))
+        && parts[parts.len() - 1].parse::<u8>().is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_file_means_defaults() {
+        let dir = std::env::temp_dir().join(format!("rcwi-config-{}", std::process::id()));
+
+        let config = Config::load(&dir).unwrap();
+
+        assert_eq!(config.query(), DEFAULT_QUERY);
+        assert_eq!(config.poll_interval(), Duration::from_millis(DEFAULT_POLL_INTERVAL_MS));
+        assert_eq!(config.log_level(), DEFAULT_LOG_LEVEL);
+        assert!(config.log_groups.is_empty());
+    }
+
+    #[test]
+    fn values_are_read() {
+        let config = Config::parse(
+            "region = \"eu-west-1\"\nquery = \"fields @message\"\nstart = \"15m\"\n\
+             log_groups = [\"/a\"]\npoll_interval_ms = 1000",
+        )
+        .unwrap();
+
+        assert_eq!(config.region.as_deref(), Some("eu-west-1"));
+        assert_eq!(config.query(), "fields @message");
+        assert_eq!(config.time_selector().unwrap().selected_start_string, "15m");
+        assert_eq!(config.log_groups, vec!["/a"]);
+        assert_eq!(config.poll_interval(), Duration::from_millis(1000));
+    }
+
+    #[test]
+    fn bad_values_are_rejected_naming_the_key() {
+        let cases = [
+            ("start = \"15x\"", "start = '15x'"),
+            ("end = \"yesterday\"", "end = 'yesterday'"),
+            ("region = \"mars-1\"", "region 'mars-1'"),
+            ("region = \"eu west 1\"", "region 'eu west 1'"),
+            ("poll_interval_ms = 0", "poll_interval_ms"),
+            ("poll_interval_ms = \"fast\"", "poll_interval_ms"),
+            ("log_level = \"app=loud\"", "log_level 'app=loud'"),
+            ("colour = true", "colour"),
+            ("timezone = \"CEST\"", "timezone 'CEST'"),
+            ("timezone = \"+25:00\"", "timezone '+25:00'"),
+        ];
+        for (content, message) in cases {
+            let err = Config::parse(content).err().map(|err| err.to_string()).unwrap_or_default();
+            assert!(err.contains(message), "{}: {}", content, err);
+        }
+    }
+
+    #[test]
+    fn regions_are_recognised_by_their_shape() {
+        for region in ["eu-west-1", "us-east-2", "ap-southeast-4", "us-gov-west-1", "il-central-1"]
+        {
+            assert!(is_region(region), "{}", region);
+        }
+        for region in ["", "eu", "eu-west", "EU-WEST-1", "eu--1", "eu-west-x", "eu-west-1a"] {
+            assert!(!is_region(region), "{}", region);
+        }
+    }
+}
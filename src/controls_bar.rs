@@ -14,8 +14,23 @@ pub(crate) fn draw(frame: &mut Frame<CrosstermBackend<io::Stdout>>, area: Rect)
         "hjkl/Arrows (control focus)",
         "Enter (select)",
         "Escape (go back)",
+        "i (edit query in place)",
+        "E (edit query in $EDITOR)",
         "r (run the query)",
+        "c (cancel the query)",
+        "T/X (open/close tab)",
+        "[/] (previous/next tab)",
+        "s (saved queries)",
+        "H (query history)",
+        "P (profile and region)",
+        "t (tail log groups)",
+        "e (export results)",
+        "v (chart/table)",
+        "g (histogram)",
+        "/ (search results)",
+        "n/N (next/previous match)",
         "y (yank row to clipboard)",
+        "Enter on row (show log record)",
     ];
     let controls_bar = Paragraph::new(controls.join(" | "))
         .style(Style::default())
@@ -0,0 +1,227 @@
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+use aws_sdk_cloudwatchlogs::{
+    error::{
+        DescribeLogGroupsError, FilterLogEventsError, GetLogGroupFieldsError, GetLogRecordError,
+        GetQueryResultsError, StartQueryError, StopQueryError,
+    },
+    model::ResultField,
+    Client, SdkError,
+};
+
+use super::{
+    backend::{
+        BackendResult, LogEvent, LogEventsPage, LogGroupField, LogGroupsPage, LogsBackend,
+        QueryResults, QueryStatistics, QueryStatus,
+    },
+    error::{BackendError, ErrorKind},
+};
+use crate::overview::ResultRow;
+
+pub(crate) struct AwsBackend {
+    client: Client,
+    region: Option<String>,
+}
+
+impl AwsBackend {
+    pub(crate) fn new(client: Client, region: Option<String>) -> Self {
+        Self { client, region }
+    }
+
+    pub(crate) fn region(&self) -> Option<&str> {
+        self.region.as_deref()
+    }
+}
+
+#[async_trait]
+impl LogsBackend for AwsBackend {
+    async fn describe_log_groups(
+        &self,
+        next_token: Option<String>,
+    ) -> BackendResult<LogGroupsPage> {
+        let mut req = self.client.describe_log_groups();
+        if let Some(next_token) = next_token {
+            req = req.next_token(next_token);
+        }
+        let res = req.send().await?;
+        Ok(LogGroupsPage {
+            log_group_names: res
+                .log_groups
+                .unwrap_or_default()
+                .into_iter()
+                .filter_map(|g| g.log_group_name)
+                .collect(),
+            next_token: res.next_token,
+        })
+    }
+
+    async fn start_query(
+        &self,
+        log_group_names: Vec<String>,
+        query_string: String,
+        start_time: i64,
+        end_time: i64,
+    ) -> BackendResult<String> {
+        let res = self
+            .client
+            .start_query()
+            .set_log_group_names(Some(log_group_names))
+            .query_string(query_string)
+            .start_time(start_time)
+            .end_time(end_time)
+            .send()
+            .await
+            .map_err(|err| BackendError::from(err).starting_query())?;
+        res.query_id.ok_or_else(|| "StartQuery returned no query id".into())
+    }
+
+    async fn get_query_results(&self, query_id: &str) -> BackendResult<QueryResults> {
+        let res = self.client.get_query_results().query_id(query_id).send().await?;
+        Ok(QueryResults {
+            status: res.status.map(|s| to_query_status(s.as_str())).unwrap_or(QueryStatus::Unknown),
+            results: to_result_rows(res.results.unwrap_or_default()),
+            statistics: res.statistics.map(|s| QueryStatistics {
+                records_matched: s.records_matched,
+                records_scanned: s.records_scanned,
+                bytes_scanned: s.bytes_scanned,
+            }),
+        })
+    }
+
+    async fn stop_query(&self, query_id: &str) -> BackendResult<bool> {
+        let res = self.client.stop_query().query_id(query_id).send().await?;
+        Ok(res.success)
+    }
+
+    async fn get_log_record(
+        &self,
+        log_record_pointer: &str,
+    ) -> BackendResult<HashMap<String, String>> {
+        let res =
+            self.client.get_log_record().log_record_pointer(log_record_pointer).send().await?;
+        Ok(res.log_record.unwrap_or_default())
+    }
+
+    async fn get_log_group_fields(
+        &self,
+        log_group_name: &str,
+    ) -> BackendResult<Vec<LogGroupField>> {
+        let res = self.client.get_log_group_fields().log_group_name(log_group_name).send().await?;
+        Ok(res
+            .log_group_fields
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|f| Some(LogGroupField { name: f.name?, percent: f.percent }))
+            .collect())
+    }
+
+    async fn filter_log_events(
+        &self,
+        log_group_name: &str,
+        filter_pattern: Option<String>,
+        start_time: i64,
+        next_token: Option<String>,
+    ) -> BackendResult<LogEventsPage> {
+        let res = self
+            .client
+            .filter_log_events()
+            .log_group_name(log_group_name)
+            .set_filter_pattern(filter_pattern)
+            .start_time(start_time)
+            .set_next_token(next_token)
+            .send()
+            .await?;
+        Ok(LogEventsPage {
+            events: res
+                .events
+                .unwrap_or_default()
+                .into_iter()
+                .map(|e| LogEvent {
+                    event_id: e.event_id.unwrap_or_default(),
+                    timestamp: e.timestamp.unwrap_or_default(),
+                    log_stream_name: e.log_stream_name.unwrap_or_default(),
+                    message: e.message.unwrap_or_default(),
+                })
+                .collect(),
+            next_token: res.next_token,
+        })
+    }
+}
+
+fn to_query_status(status: &str) -> QueryStatus {
+    match status {
+        "Scheduled" => QueryStatus::Scheduled,
+        "Running" => QueryStatus::Running,
+        "Complete" => QueryStatus::Complete,
+        "Failed" => QueryStatus::Failed,
+        "Cancelled" => QueryStatus::Cancelled,
+        "Timeout" => QueryStatus::Timeout,
+        _ => QueryStatus::Unknown,
+    }
+}
+
+fn to_result_rows(results: Vec<Vec<ResultField>>) -> Vec<ResultRow> {
+    results
+        .into_iter()
+        .map(|row| ResultRow {
+            fields: row
+                .into_iter()
+                .filter_map(|e| Some((e.field?, e.value.unwrap_or_default())))
+                .collect(),
+        })
+        .collect()
+}
+
+/// The details every operation error of the SDK has.
+trait ServiceError: std::fmt::Debug {
+    fn code(&self) -> Option<&str>;
+    fn message(&self) -> Option<&str>;
+    fn request_id(&self) -> Option<&str>;
+}
+
+macro_rules! service_errors {
+    ($($error:ty),*) => {$(
+        impl ServiceError for $error {
+            fn code(&self) -> Option<&str> {
+                <$error>::code(self)
+            }
+            fn message(&self) -> Option<&str> {
+                <$error>::message(self)
+            }
+            fn request_id(&self) -> Option<&str> {
+                <$error>::request_id(self)
+            }
+        }
+    )*};
+}
+
+service_errors!(
+    DescribeLogGroupsError,
+    StartQueryError,
+    GetQueryResultsError,
+    StopQueryError,
+    GetLogRecordError,
+    GetLogGroupFieldsError,
+    FilterLogEventsError
+);
+
+impl<E: ServiceError> From<SdkError<E>> for BackendError {
+    fn from(err: SdkError<E>) -> Self {
+        match err {
+            SdkError::ServiceError { err, .. } => BackendError {
+                kind: err.code().map(ErrorKind::from_code).unwrap_or(ErrorKind::Other),
+                code: err.code().map(String::from),
+                message: match err.message() {
+                    Some(message) => message.to_string(),
+                    None => format!("{:?}", err),
+                },
+                request_id: err.request_id().map(String::from),
+            },
+            SdkError::DispatchFailure(err) => {
+                BackendError::new(ErrorKind::Network, format!("{:?}", err))
+            }
+            err => BackendError::new(ErrorKind::Other, format!("{:?}", err)),
+        }
+    }
+}
@@ -0,0 +1,123 @@
+use std::{collections::HashMap, fmt::Display};
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+use super::error::BackendError;
+use crate::overview::ResultRow;
+
+pub(crate) type BackendResult<T> = Result<T, BackendError>;
+
+/// The CloudWatch Logs operations used by the worker thread.
+///
+/// Implemented by [`AwsBackend`](super::aws::AwsBackend) for the real service so that the
+/// request handling in [`cwl`](super) can be driven by something else in tests.
+#[async_trait]
+pub(crate) trait LogsBackend: Send + Sync {
+    async fn describe_log_groups(&self, next_token: Option<String>)
+        -> BackendResult<LogGroupsPage>;
+
+    /// Starts an Insights query and returns its query id.
+    async fn start_query(
+        &self,
+        log_group_names: Vec<String>,
+        query_string: String,
+        start_time: i64,
+        end_time: i64,
+    ) -> BackendResult<String>;
+
+    async fn get_query_results(&self, query_id: &str) -> BackendResult<QueryResults>;
+
+    async fn stop_query(&self, query_id: &str) -> BackendResult<bool>;
+
+    async fn get_log_record(
+        &self,
+        log_record_pointer: &str,
+    ) -> BackendResult<HashMap<String, String>>;
+
+    /// Fields discovered in a log group over the last 15 minutes.
+    async fn get_log_group_fields(&self, log_group_name: &str)
+        -> BackendResult<Vec<LogGroupField>>;
+
+    /// Events of a single log group from `start_time` (milliseconds) on, oldest first.
+    async fn filter_log_events(
+        &self,
+        log_group_name: &str,
+        filter_pattern: Option<String>,
+        start_time: i64,
+        next_token: Option<String>,
+    ) -> BackendResult<LogEventsPage>;
+}
+
+#[derive(Clone, Debug, Default)]
+pub(crate) struct LogGroupsPage {
+    pub(crate) log_group_names: Vec<String>,
+    pub(crate) next_token: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub(crate) struct LogGroupField {
+    pub(crate) name: String,
+    /// Percentage of the log events the field was found in.
+    pub(crate) percent: i32,
+}
+
+#[derive(Clone, Debug, Default)]
+pub(crate) struct LogEventsPage {
+    pub(crate) events: Vec<LogEvent>,
+    pub(crate) next_token: Option<String>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub(crate) struct LogEvent {
+    pub(crate) event_id: String,
+    /// Milliseconds since the epoch.
+    pub(crate) timestamp: i64,
+    pub(crate) log_stream_name: String,
+    pub(crate) message: String,
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct QueryResults {
+    pub(crate) status: QueryStatus,
+    pub(crate) results: Vec<ResultRow>,
+    pub(crate) statistics: Option<QueryStatistics>,
+}
+
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize)]
+pub(crate) struct QueryStatistics {
+    pub(crate) records_matched: f64,
+    pub(crate) records_scanned: f64,
+    pub(crate) bytes_scanned: f64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) enum QueryStatus {
+    Scheduled,
+    Running,
+    Complete,
+    Failed,
+    Cancelled,
+    Timeout,
+    Unknown,
+}
+
+impl QueryStatus {
+    pub(crate) fn is_done(&self) -> bool {
+        !matches!(self, QueryStatus::Scheduled | QueryStatus::Running)
+    }
+}
+
+impl Display for QueryStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            QueryStatus::Scheduled => "Scheduled",
+            QueryStatus::Running => "Running",
+            QueryStatus::Complete => "Complete",
+            QueryStatus::Failed => "Failed",
+            QueryStatus::Cancelled => "Cancelled",
+            QueryStatus::Timeout => "Timeout",
+            QueryStatus::Unknown => "Unknown",
+        })
+    }
+}
@@ -0,0 +1,170 @@
+use std::{error::Error, fmt::Display};
+
+/// What went wrong in a request, decides the summary shown in the status bar.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum ErrorKind {
+    Throttling,
+    AccessDenied,
+    MalformedQuery,
+    /// A parameter was rejected, only a problem with the query when starting one.
+    InvalidParameter,
+    LimitExceeded,
+    ExpiredCredentials,
+    Network,
+    Other,
+}
+
+impl ErrorKind {
+    /// Classifies an error code returned by the service.
+    pub(crate) fn from_code(code: &str) -> Self {
+        match code {
+            "ThrottlingException"
+            | "Throttling"
+            | "TooManyRequestsException"
+            | "RequestLimitExceeded" => ErrorKind::Throttling,
+            "AccessDeniedException" | "AccessDenied" | "UnauthorizedOperation" => {
+                ErrorKind::AccessDenied
+            }
+            "MalformedQueryException" => ErrorKind::MalformedQuery,
+            "InvalidParameterException" => ErrorKind::InvalidParameter,
+            "LimitExceededException" | "ServiceQuotaExceededException" => ErrorKind::LimitExceeded,
+            "ExpiredTokenException"
+            | "ExpiredToken"
+            | "UnrecognizedClientException"
+            | "InvalidSignatureException" => ErrorKind::ExpiredCredentials,
+            _ => ErrorKind::Other,
+        }
+    }
+}
+
+/// A failed request to CloudWatch Logs with whatever the service told about it.
+#[derive(Clone, Debug)]
+pub(crate) struct BackendError {
+    pub(crate) kind: ErrorKind,
+    pub(crate) code: Option<String>,
+    pub(crate) message: String,
+    pub(crate) request_id: Option<String>,
+}
+
+impl BackendError {
+    pub(crate) fn new(kind: ErrorKind, message: impl Into<String>) -> Self {
+        Self { kind, code: None, message: message.into(), request_id: None }
+    }
+
+    /// The error of a `StartQuery` request, where an invalid parameter is the query.
+    pub(crate) fn starting_query(mut self) -> Self {
+        if self.kind == ErrorKind::InvalidParameter {
+            self.kind = ErrorKind::MalformedQuery;
+        }
+        self
+    }
+
+    /// A short description of the problem and what to do about it.
+    pub(crate) fn summary(&self) -> &'static str {
+        match self.kind {
+            ErrorKind::Throttling => "throttled by AWS, try again in a moment",
+            ErrorKind::AccessDenied => "access denied, check the permissions of the profile",
+            ErrorKind::MalformedQuery => "the query was rejected as malformed",
+            ErrorKind::InvalidParameter => {
+                "the request was rejected as invalid, check the log group names"
+            }
+            ErrorKind::LimitExceeded => "a service limit was exceeded",
+            ErrorKind::ExpiredCredentials => "the credentials are invalid or have expired",
+            ErrorKind::Network => "AWS could not be reached, check the network connection",
+            ErrorKind::Other => "the request failed",
+        }
+    }
+
+    /// Everything known about the error, one item per line.
+    pub(crate) fn detail(&self) -> String {
+        let mut lines = vec![format!("Error: {}", self.summary())];
+        if let Some(code) = &self.code {
+            lines.push(format!("Code: {}", code));
+        }
+        lines.push(format!("Message: {}", self.message));
+        if let Some(request_id) = &self.request_id {
+            lines.push(format!("Request id: {}", request_id));
+        }
+        lines.join("\n")
+    }
+}
+
+impl Display for BackendError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.code {
+            Some(code) => write!(f, "{} ({}: {})", self.summary(), code, self.message),
+            None => write!(f, "{} ({})", self.summary(), self.message),
+        }
+    }
+}
+
+impl Error for BackendError {}
+
+impl From<&str> for BackendError {
+    fn from(message: &str) -> Self {
+        BackendError::new(ErrorKind::Other, message)
+    }
+}
+
+impl From<String> for BackendError {
+    fn from(message: String) -> Self {
+        BackendError::new(ErrorKind::Other, message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn codes_are_classified() {
+        let cases = [
+            ("ThrottlingException", ErrorKind::Throttling),
+            ("TooManyRequestsException", ErrorKind::Throttling),
+            ("AccessDeniedException", ErrorKind::AccessDenied),
+            ("MalformedQueryException", ErrorKind::MalformedQuery),
+            ("InvalidParameterException", ErrorKind::InvalidParameter),
+            ("LimitExceededException", ErrorKind::LimitExceeded),
+            ("ExpiredTokenException", ErrorKind::ExpiredCredentials),
+            ("UnrecognizedClientException", ErrorKind::ExpiredCredentials),
+            ("ResourceNotFoundException", ErrorKind::Other),
+        ];
+        for (code, kind) in cases {
+            assert_eq!(ErrorKind::from_code(code), kind, "{}", code);
+        }
+    }
+
+    #[test]
+    fn invalid_parameters_are_malformed_queries_only_when_starting_one() {
+        let err = BackendError::new(ErrorKind::InvalidParameter, "bad");
+        assert_eq!(err.clone().starting_query().kind, ErrorKind::MalformedQuery);
+        assert_eq!(err.summary(), "the request was rejected as invalid, check the log group names");
+        let throttled = BackendError::new(ErrorKind::Throttling, "slow down").starting_query();
+        assert_eq!(throttled.kind, ErrorKind::Throttling);
+    }
+
+    #[test]
+    fn detail_lists_everything_known() {
+        let err = BackendError {
+            kind: ErrorKind::AccessDenied,
+            code: Some("AccessDeniedException".to_string()),
+            message: "not allowed".to_string(),
+            request_id: Some("request-1".to_string()),
+        };
+        assert_eq!(
+            err.detail(),
+            "Error: access denied, check the permissions of the profile\n\
+             Code: AccessDeniedException\n\
+             Message: not allowed\n\
+             Request id: request-1"
+        );
+        assert_eq!(
+            err.to_string(),
+            "access denied, check the permissions of the profile (AccessDeniedException: not allowed)"
+        );
+
+        let err = BackendError::from("no query id");
+        assert_eq!(err.detail(), "Error: the request failed\nMessage: no query id");
+        assert_eq!(err.to_string(), "the request failed (no query id)");
+    }
+}
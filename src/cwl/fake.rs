@@ -0,0 +1,259 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::Mutex,
+    time::Duration,
+};
+
+use async_trait::async_trait;
+
+use super::{
+    backend::{
+        BackendResult, LogEvent, LogEventsPage, LogGroupField, LogGroupsPage, LogsBackend,
+        QueryResults, QueryStatus,
+    },
+    error::{BackendError, ErrorKind},
+    MAX_ROWS,
+};
+use crate::overview::ResultRow;
+
+/// In-memory [`LogsBackend`] replaying canned responses.
+///
+/// Log group pages are served in order using the page index as the next token. Every call
+/// to `get_query_results` pops the next canned response, the last one is repeated once the
+/// queue runs dry. Log events are filtered like `FilterLogEvents` does, with the filter pattern
+/// matching as a plain substring. Every request fails when an error is set, and the first ones
+/// when requests are throttled.
+///
+/// With query events set, query results are those events in the time range of the query
+/// instead, newest first and cut off at [`MAX_ROWS`] like Insights does.
+#[derive(Default)]
+pub(crate) struct FakeBackend {
+    log_group_pages: Vec<Vec<String>>,
+    query_results: Mutex<VecDeque<QueryResults>>,
+    log_records: HashMap<String, HashMap<String, String>>,
+    log_events: HashMap<String, Vec<LogEvent>>,
+    log_group_fields: HashMap<String, Vec<LogGroupField>>,
+    pub(crate) fields_requests: Mutex<Vec<String>>,
+    error: Option<BackendError>,
+    throttled_requests: Mutex<u32>,
+    pub(crate) started_queries: Mutex<Vec<String>>,
+    pub(crate) stopped_queries: Mutex<Vec<String>>,
+    query_events: Option<Vec<i64>>,
+    /// Time range of every started query, in order.
+    pub(crate) query_windows: Mutex<Vec<(i64, i64)>>,
+    start_delay: Option<Duration>,
+}
+
+impl FakeBackend {
+    pub(crate) fn with_log_group_pages(mut self, pages: Vec<Vec<&str>>) -> Self {
+        self.log_group_pages =
+            pages.into_iter().map(|p| p.into_iter().map(String::from).collect()).collect();
+        self
+    }
+
+    pub(crate) fn with_query_results(self, results: Vec<QueryResults>) -> Self {
+        *self.query_results.lock().unwrap() = results.into();
+        self
+    }
+
+    /// Timestamps of the events every query matches, each one a row with its timestamp as
+    /// `@message`.
+    pub(crate) fn with_query_events(mut self, timestamps: Vec<i64>) -> Self {
+        self.query_events = Some(timestamps);
+        self
+    }
+
+    /// Events of `log_group` given as `(timestamp, message)`, the event id is the message.
+    pub(crate) fn with_log_events(mut self, log_group: &str, events: Vec<(i64, &str)>) -> Self {
+        self.log_events.insert(
+            log_group.to_string(),
+            events
+                .into_iter()
+                .map(|(timestamp, message)| LogEvent {
+                    event_id: message.to_string(),
+                    timestamp,
+                    log_stream_name: "stream".to_string(),
+                    message: message.to_string(),
+                })
+                .collect(),
+        );
+        self
+    }
+
+    /// Fields of `log_group` given as `(name, percent)`.
+    pub(crate) fn with_log_group_fields(
+        mut self,
+        log_group: &str,
+        fields: Vec<(&str, i32)>,
+    ) -> Self {
+        self.log_group_fields.insert(
+            log_group.to_string(),
+            fields
+                .into_iter()
+                .map(|(name, percent)| LogGroupField { name: name.to_string(), percent })
+                .collect(),
+        );
+        self
+    }
+
+    /// Fails every request with a service error with `code`, classified like the real one.
+    pub(crate) fn with_error(mut self, code: &str, message: &str) -> Self {
+        self.error = Some(BackendError {
+            kind: ErrorKind::from_code(code),
+            code: Some(code.to_string()),
+            message: message.to_string(),
+            request_id: Some("request-1".to_string()),
+        });
+        self
+    }
+
+    /// Makes starting a query take `delay`.
+    pub(crate) fn with_start_delay(mut self, delay: Duration) -> Self {
+        self.start_delay = Some(delay);
+        self
+    }
+
+    /// Fails the next `count` requests with a throttling error.
+    pub(crate) fn with_throttled_requests(self, count: u32) -> Self {
+        *self.throttled_requests.lock().unwrap() = count;
+        self
+    }
+
+    fn check_error(&self) -> BackendResult<()> {
+        if let Some(err) = &self.error {
+            return Err(err.clone());
+        }
+        let mut throttled = self.throttled_requests.lock().unwrap();
+        if *throttled > 0 {
+            *throttled -= 1;
+            return Err(BackendError {
+                kind: ErrorKind::Throttling,
+                code: Some("ThrottlingException".to_string()),
+                message: "Rate exceeded".to_string(),
+                request_id: None,
+            });
+        }
+        Ok(())
+    }
+
+    pub(crate) fn with_log_record(mut self, ptr: &str, record: Vec<(&str, &str)>) -> Self {
+        self.log_records.insert(
+            ptr.to_string(),
+            record.into_iter().map(|(k, v)| (k.to_string(), v.to_string())).collect(),
+        );
+        self
+    }
+}
+
+#[async_trait]
+impl LogsBackend for FakeBackend {
+    async fn describe_log_groups(
+        &self,
+        next_token: Option<String>,
+    ) -> BackendResult<LogGroupsPage> {
+        self.check_error()?;
+        let page = match next_token {
+            Some(token) => token.parse::<usize>().map_err(|err| err.to_string())?,
+            None => 0,
+        };
+        let log_group_names = self.log_group_pages.get(page).cloned().unwrap_or_default();
+        let next_token =
+            if page + 1 < self.log_group_pages.len() { Some((page + 1).to_string()) } else { None };
+        Ok(LogGroupsPage { log_group_names, next_token })
+    }
+
+    async fn start_query(
+        &self,
+        _log_group_names: Vec<String>,
+        query_string: String,
+        start_time: i64,
+        end_time: i64,
+    ) -> BackendResult<String> {
+        if let Some(delay) = self.start_delay {
+            tokio::time::sleep(delay).await;
+        }
+        self.check_error()?;
+        self.query_windows.lock().unwrap().push((start_time, end_time));
+        let mut started = self.started_queries.lock().unwrap();
+        started.push(query_string);
+        Ok(format!("query-{}", started.len()))
+    }
+
+    async fn get_query_results(&self, query_id: &str) -> BackendResult<QueryResults> {
+        self.check_error()?;
+        if let Some(events) = &self.query_events {
+            let index = query_id.trim_start_matches("query-").parse::<usize>().unwrap_or(0);
+            let (start, end) = self.query_windows.lock().unwrap()[index - 1];
+            let mut matching: Vec<i64> =
+                events.iter().copied().filter(|t| *t >= start && *t <= end).collect();
+            matching.sort_unstable_by(|a, b| b.cmp(a));
+            let results = matching
+                .into_iter()
+                .take(MAX_ROWS)
+                .map(|t| ResultRow { fields: vec![("@message".to_string(), t.to_string())] })
+                .collect();
+            return Ok(QueryResults { status: QueryStatus::Complete, results, statistics: None });
+        }
+        let mut results = self.query_results.lock().unwrap();
+        let res = if results.len() > 1 { results.pop_front() } else { results.front().cloned() };
+        res.ok_or_else(|| "no canned query results".into())
+    }
+
+    async fn stop_query(&self, query_id: &str) -> BackendResult<bool> {
+        self.check_error()?;
+        self.stopped_queries.lock().unwrap().push(query_id.to_string());
+        let mut results = self.query_results.lock().unwrap();
+        let last = match results.front() {
+            Some(last) => QueryResults { status: QueryStatus::Cancelled, ..last.clone() },
+            None => return Ok(false),
+        };
+        *results = vec![last].into();
+        Ok(true)
+    }
+
+    async fn get_log_record(
+        &self,
+        log_record_pointer: &str,
+    ) -> BackendResult<HashMap<String, String>> {
+        self.check_error()?;
+        self.log_records
+            .get(log_record_pointer)
+            .cloned()
+            .ok_or_else(|| format!("no log record for {}", log_record_pointer).into())
+    }
+
+    async fn get_log_group_fields(
+        &self,
+        log_group_name: &str,
+    ) -> BackendResult<Vec<LogGroupField>> {
+        self.check_error()?;
+        self.fields_requests.lock().unwrap().push(log_group_name.to_string());
+        self.log_group_fields
+            .get(log_group_name)
+            .cloned()
+            .ok_or_else(|| format!("no log group {}", log_group_name).into())
+    }
+
+    async fn filter_log_events(
+        &self,
+        log_group_name: &str,
+        filter_pattern: Option<String>,
+        start_time: i64,
+        _next_token: Option<String>,
+    ) -> BackendResult<LogEventsPage> {
+        self.check_error()?;
+        let events = self
+            .log_events
+            .get(log_group_name)
+            .into_iter()
+            .flatten()
+            .filter(|e| e.timestamp >= start_time)
+            .filter(|e| match &filter_pattern {
+                Some(pattern) => e.message.contains(pattern.as_str()),
+                None => true,
+            })
+            .cloned()
+            .collect();
+        Ok(LogEventsPage { events, next_token: None })
+    }
+}
@@ -1,18 +1,75 @@
 use std::{
-    collections::HashMap,
-    sync::{mpsc::Receiver, Arc, Mutex},
+    cell::Cell,
+    collections::HashSet,
+    future::Future,
+    sync::{
+        mpsc::{Receiver, TryRecvError},
+        Arc, Mutex,
+    },
     time::Duration,
 };
 
-use aws_sdk_cloudwatchlogs::Client;
+use aws_config::profile::{ProfileFileCredentialsProvider, ProfileFileRegionProvider};
+use aws_sdk_cloudwatchlogs::{Client, Region};
+use futures::{
+    future::{join, join_all, select, Either, FutureExt, LocalBoxFuture},
+    stream::{FuturesUnordered, StreamExt},
+};
 use indicium::simple::{Indexable, SearchIndex};
 use log::{error, info};
 
-use crate::{log_groups::filter_log_groups, overview::QueryLogRow, status_bar::StatusMessage, App};
+use crate::{
+    chart::bin_column,
+    config::Config,
+    fields::Fields,
+    histogram::{bin_size, histogram_query, Histogram},
+    history::HistoryEntry,
+    log_groups::filter_log_groups,
+    overview::{QueryProgress, ResultRow},
+    profiles::active_profile,
+    query_lint::lint,
+    query_syntax::{command_is, commands},
+    status_bar::StatusMessage,
+    tabs,
+    tail::to_row,
+    time_select::now,
+    App,
+};
+
+use self::{
+    aws::AwsBackend,
+    backend::{BackendResult, LogEvent, LogsBackend, QueryResults, QueryStatistics, QueryStatus},
+    error::BackendError,
+    retry::{retry_message, with_retry, RetryPolicy},
+};
+
+pub(crate) mod aws;
+pub(crate) mod backend;
+pub(crate) mod error;
+#[cfg(test)]
+pub(crate) mod fake;
+pub(crate) mod retry;
+
+/// How far back a tail starts, so there is something to look at right away.
+const TAIL_BACKFILL_MS: i64 = 60_000;
+
+/// How often the worker checks for new requests while queries are running.
+const REQUEST_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Insights returns at most this many rows for a query.
+pub(crate) const MAX_ROWS: usize = 10_000;
 
 pub(crate) enum AwsReq {
     ListLogGroups,
-    RunQuery,
+    /// Run the query of the tab with the given id.
+    RunQuery(usize),
+    GetLogRecord(String),
+    /// Rebuild the client with the profile and region currently in the config.
+    Connect,
+    /// Follow the log groups in the tail view until it is closed.
+    Tail,
+    /// Fetch the fields of newly selected log groups.
+    GetLogGroupFields,
 }
 
 struct MyString {
@@ -30,148 +87,1569 @@ impl Indexable for MyString {
     }
 }
 
+/// A backend a query is run in.
+pub(crate) struct Target<'a> {
+    /// Region and profile added to every result row, `None` when the query isn't fanned out.
+    pub(crate) source: Option<(String, String)>,
+    pub(crate) backend: &'a dyn LogsBackend,
+}
+
+impl Target<'_> {
+    fn name(&self) -> Option<String> {
+        self.source.as_ref().map(|(region, profile)| format!("{}/{}", region, profile))
+    }
+}
+
+/// A query run in a tab, the same for every target.
+struct QueryRun<'a> {
+    tab: usize,
+    query: &'a str,
+    log_groups: &'a [String],
+    start: i64,
+    end: i64,
+    descending: bool,
+    /// How many time windows run at once when the query hits [`MAX_ROWS`] and is split up,
+    /// `None` when it isn't split.
+    slices: Option<usize>,
+}
+
 pub(crate) fn run(app: Arc<Mutex<App>>, rx: Receiver<AwsReq>) {
     let basic_rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
     basic_rt.block_on(async {
-        let shared_config = aws_config::load_from_env().await;
-        let client = Client::new(&shared_config);
+        let mut connected = None;
         loop {
-            if let Ok(req) = rx.recv() {
-                match req {
-                    AwsReq::ListLogGroups => {
-                        {
-                            let mut app_ = app.lock().unwrap();
-                            app_.status_message = StatusMessage::info("Log groups request started");
-                        }
-                        let res = client.describe_log_groups().send().await;
-                        match res {
-                            Ok(mut res) => {
-                                {
-                                    let names: Vec<String> = res
-                                        .log_groups
-                                        .unwrap_or(vec![])
-                                        .iter()
-                                        .map(|z| z.log_group_name.as_ref().unwrap().clone())
-                                        .collect();
-                                    let mut app_ = app.lock().unwrap();
-                                    app_.log_groups.log_group_search_index = SearchIndex::default();
-                                    names
-                                        .iter()
-                                        .map(|x| MyString::from(x.as_str()))
-                                        .enumerate()
-                                        .for_each(|(index, element)| {
-                                            app_.log_groups.log_group_search_index.insert(&index, &element)
-                                        });
-                                    app_.log_groups.log_groups = names;
-                                    filter_log_groups(&mut app_);
-                                }
-                                loop {
-                                    if res.next_token.is_none() {
-                                        break;
-                                    }
-                                    res = client
-                                        .describe_log_groups()
-                                        .next_token(res.next_token.as_ref().unwrap())
-                                        .send()
-                                        .await
-                                        .unwrap();
-                                    let names: Vec<String> = res
-                                        .log_groups
-                                        .unwrap_or(vec![])
-                                        .iter()
-                                        .map(|z| z.log_group_name.as_ref().unwrap().clone())
-                                        .collect();
-
-                                    {
-                                        let mut app_ = app.lock().unwrap();
-                                        let num_log_groups = app_.log_groups.log_groups.len();
-                                        names
-                                            .iter()
-                                            .map(|x| MyString::from(x.as_str()))
-                                            .enumerate()
-                                            .for_each(|(index, element)| {
-                                                app_.log_groups.log_group_search_index.insert(&(num_log_groups + index), &element)
-                                            });
-                                        app_.log_groups.log_groups.extend(names);
-                                        filter_log_groups(&mut app_);
-                                    }
-                                }
-                                let mut app_ = app.lock().unwrap();
-                                app_.status_message = StatusMessage::info("Log groups request completed");
-                            },
-                            Err(err) => {
-                                error!("{:?}", err);
-                                let mut app_ = app.lock().unwrap();
-                                app_.status_message = StatusMessage::error("Log groups request failed");
-                            },
-                        }
+            let config = app.lock().unwrap().config.clone();
+            let backend = aws_backend(&config).await;
+            let mut target_backends = vec![];
+            for target in &config.targets {
+                let target_config = config.for_target(target);
+                let source = (target.region.clone(), active_profile(&target_config));
+                target_backends.push((source, aws_backend(&target_config).await));
+            }
+            let targets: Vec<Target> = target_backends
+                .iter()
+                .map(|(source, backend)| Target { source: Some(source.clone()), backend })
+                .collect();
+            {
+                let mut app_ = app.lock().unwrap();
+                app_.aws_region = Some(backend.region().unwrap_or("none").to_string());
+                let primary = (config.profile.clone(), config.region.clone());
+                if let Some(previous) = connected.replace(primary.clone()) {
+                    // Log groups belong to the account and region, fan-out targets don't matter.
+                    if previous != primary {
+                        app_.log_groups.log_groups = vec![];
+                        app_.log_groups.log_group_search_index = SearchIndex::default();
+                        filter_log_groups(&mut app_);
+                        app_.fields = Fields::default();
                     }
-                    AwsReq::RunQuery => {
-                        let (log_groups, query_string, start, end) = {
-                            let mut app_ = app.lock().unwrap();
-                            let log_groups = app_.log_groups.selected_log_groups.clone();
-                            let (start, end) = app_.time_selector.to_timestamps();
-                            app_.status_message = StatusMessage::info("Cloudwatch Insights query started");
-                            (log_groups, app_.query.clone(), start, end)
-                        };
-                        let res = client
-                            .start_query()
-                            .set_log_group_names(Some(log_groups))
-                            .query_string(query_string)
-                            .start_time(start)
-                            .end_time(end)
-                            .send()
-                            .await;
-                        match res {
-                            Ok(res) => {
-
-                                if let Some(query_id) = res.query_id {
-                                    let mut res;
-                                    loop {
-                                        res = client.get_query_results().query_id(query_id.clone()).send().await.unwrap();
-                                        match res.status {
-                                            Some(x) if x != aws_sdk_cloudwatchlogs::model::QueryStatus::Running => break,
-                                            _ => {
-                                                info!("query: {:?}", res);
-                                                if let Some(results) = res.results {
-                                                    let mut app_ = app.lock().unwrap();
-                                                    app_.log_results.query_results = results.into_iter().map(|x| {
-                                                        let mut map: HashMap<String, String> = x.into_iter().map(|e| (e.field.unwrap(), e.value.unwrap())).collect();
-                                                        QueryLogRow {
-                                                            message: map.remove("@message").unwrap(),
-                                                            timestamp: map.remove("@timestamp").unwrap(),
-                                                            ptr: map.remove("@ptr").unwrap(),
-                                                        }
-                                                    }).collect::<Vec<_>>();
-                                                }
-                                            },
-                                        }
-                                        tokio::time::sleep(Duration::from_millis(500)).await;
-                                    }
-                                    if let Some(results) = res.results {
-                                        let mut app_ = app.lock().unwrap();
-                                        app_.log_results.query_results = results.into_iter().map(|x| {
-                                            let mut map: HashMap<String, String> = x.into_iter().map(|e| (e.field.unwrap(), e.value.unwrap())).collect();
-                                            QueryLogRow {
-                                                message: map.remove("@message").unwrap(),
-                                                timestamp: map.remove("@timestamp").unwrap(),
-                                                ptr: map.remove("@ptr").unwrap(),
-                                            }
-                                        }).collect::<Vec<_>>();
-                                        app_.status_message = StatusMessage::info("Cloudwatch Insights query completed");
-                                    }
-                                }
-                            },
-                            Err(e) => {
-                                error!("{:?}", e);
-                                let mut app_ = app.lock().unwrap();
-                                app_.status_message = StatusMessage::error("Cloudwatch Insights query failed");
-                            },
-                        }
+                    let mut message = format!(
+                        "Connected with profile {} in region {}",
+                        active_profile(&app_.config),
+                        app_.aws_region.as_deref().unwrap_or("none")
+                    );
+                    if !targets.is_empty() {
+                        message += &format!(", queries run in {} targets", targets.len());
                     }
-
+                    app_.status_message = StatusMessage::info(&message);
                 }
             }
+            get_log_group_fields(&app, &backend).await;
+            if !serve(&app, &rx, &backend, &targets).await {
+                break;
+            }
         }
     });
 }
+
+/// Builds a client from the environment, with the profile and region from the config taking
+/// precedence over `AWS_PROFILE` and `AWS_REGION`.
+pub(crate) async fn aws_backend(config: &Config) -> AwsBackend {
+    let mut loader = aws_config::from_env();
+    if let Some(profile) = &config.profile {
+        loader = loader
+            .region(ProfileFileRegionProvider::builder().profile_name(profile).build())
+            .credentials_provider(
+                ProfileFileCredentialsProvider::builder().profile_name(profile).build(),
+            );
+    }
+    if let Some(region) = &config.region {
+        loader = loader.region(Region::new(region.clone()));
+    }
+    let shared_config = loader.load().await;
+    AwsBackend::new(
+        Client::new(&shared_config),
+        shared_config.region().map(|region| region.to_string()),
+    )
+}
+
+/// Handles requests from the UI thread until the sending side is dropped or a new client is
+/// requested with [`AwsReq::Connect`], returns `true` in the latter case. Running queries are
+/// finished first either way.
+///
+/// Queries are fanned out to `targets` when there are any, everything else goes to `backend`.
+/// Queries and the tail run concurrently with each other and with the other requests, which
+/// are handled one at a time.
+async fn serve(
+    app: &Arc<Mutex<App>>,
+    rx: &Receiver<AwsReq>,
+    backend: &dyn LogsBackend,
+    targets: &[Target<'_>],
+) -> bool {
+    let tailing = Cell::new(false);
+    let mut queries: FuturesUnordered<LocalBoxFuture<()>> = FuturesUnordered::new();
+    loop {
+        let req = if queries.is_empty() {
+            match rx.recv() {
+                Ok(req) => req,
+                Err(_) => return false,
+            }
+        } else {
+            match rx.try_recv() {
+                Ok(req) => req,
+                Err(TryRecvError::Empty) => {
+                    let check = Box::pin(tokio::time::sleep(REQUEST_POLL_INTERVAL));
+                    select(queries.next(), check).await;
+                    continue;
+                }
+                Err(TryRecvError::Disconnected) => {
+                    while queries.next().await.is_some() {}
+                    return false;
+                }
+            }
+        };
+        match req {
+            AwsReq::ListLogGroups => drive(list_log_groups(app, backend), &mut queries).await,
+            AwsReq::RunQuery(tab) => {
+                queries.push(run_query(app, backend, targets, tab).boxed_local())
+            }
+            AwsReq::GetLogRecord(ptr) => {
+                drive(get_log_record(app, backend, targets, &ptr), &mut queries).await
+            }
+            AwsReq::Connect => {
+                if !queries.is_empty() {
+                    app.lock().unwrap().status_message = StatusMessage::info(
+                        "Connecting once the running queries are done, cancel them with c to connect now",
+                    );
+                }
+                while queries.next().await.is_some() {}
+                return true;
+            }
+            // A tail that is still running picks up the new one, it restarts when asked to.
+            AwsReq::Tail if tailing.get() => {}
+            AwsReq::Tail => {
+                tailing.set(true);
+                queries.push(
+                    async {
+                        tail(app, backend).await;
+                        tailing.set(false);
+                    }
+                    .boxed_local(),
+                );
+            }
+            AwsReq::GetLogGroupFields => {
+                drive(get_log_group_fields(app, backend), &mut queries).await
+            }
+        }
+    }
+}
+
+/// Runs `request` to completion while the running queries keep going.
+async fn drive(
+    request: impl Future<Output = ()>,
+    queries: &mut FuturesUnordered<LocalBoxFuture<'_, ()>>,
+) {
+    let mut request = Box::pin(request);
+    while !queries.is_empty() {
+        if let Either::Left(_) = select(&mut request, queries.next()).await {
+            return;
+        }
+    }
+    request.await
+}
+
+/// Fetches the fields of selected log groups that haven't been fetched yet, then updates the
+/// fields offered for the selection.
+async fn get_log_group_fields(app: &Arc<Mutex<App>>, backend: &dyn LogsBackend) {
+    let missing: Vec<String> = {
+        let app_ = app.lock().unwrap();
+        app_.log_groups
+            .selected_log_groups
+            .iter()
+            .filter(|g| !app_.fields.by_log_group.contains_key(*g))
+            .cloned()
+            .collect()
+    };
+    for log_group in missing {
+        match backend.get_log_group_fields(&log_group).await {
+            Ok(fields) => {
+                app.lock().unwrap().fields.by_log_group.insert(log_group, fields);
+            }
+            Err(err) => {
+                error!("{:?}", err);
+                app.lock().unwrap().status_message = StatusMessage::aws_error(
+                    &format!("Fetching fields of {} failed", log_group),
+                    &err,
+                );
+            }
+        }
+    }
+    let mut app_ = app.lock().unwrap();
+    let selected = app_.log_groups.selected_log_groups.clone();
+    app_.fields.update(&selected);
+}
+
+async fn list_log_groups(app: &Arc<Mutex<App>>, backend: &dyn LogsBackend) {
+    {
+        let mut app_ = app.lock().unwrap();
+        app_.status_message = StatusMessage::info("Log groups request started");
+        app_.log_groups.log_groups = vec![];
+        app_.log_groups.log_group_search_index = SearchIndex::default();
+    }
+    let policy = app.lock().unwrap().config.retry_policy();
+    let mut next_token = None;
+    loop {
+        let page = with_retry(&policy, retry_status(app, "Log groups request", &policy), || {
+            backend.describe_log_groups(next_token.clone())
+        })
+        .await;
+        let page = match page {
+            Ok(page) => page,
+            Err(err) => {
+                error!("{:?}", err);
+                let mut app_ = app.lock().unwrap();
+                app_.status_message = StatusMessage::aws_error("Log groups request failed", &err);
+                return;
+            }
+        };
+        {
+            let mut app_ = app.lock().unwrap();
+            let num_log_groups = app_.log_groups.log_groups.len();
+            page.log_group_names.iter().map(|x| MyString::from(x.as_str())).enumerate().for_each(
+                |(index, element)| {
+                    app_.log_groups
+                        .log_group_search_index
+                        .insert(&(num_log_groups + index), &element)
+                },
+            );
+            app_.log_groups.log_groups.extend(page.log_group_names);
+            filter_log_groups(&mut app_);
+        }
+        // A token that doesn't change would page forever.
+        if page.next_token.is_none() || page.next_token == next_token {
+            break;
+        }
+        next_token = page.next_token;
+    }
+    let mut app_ = app.lock().unwrap();
+    app_.status_message = StatusMessage::info("Log groups request completed");
+}
+
+/// Runs the query of the tab `tab` into its results, the query is stopped when the tab is
+/// closed while it runs. The histogram query runs next to it.
+async fn run_query(
+    app: &Arc<Mutex<App>>,
+    backend: &dyn LogsBackend,
+    targets: &[Target<'_>],
+    tab: usize,
+) {
+    let single = [Target { source: None, backend }];
+    let targets = if targets.is_empty() { &single[..] } else { targets };
+    let (log_groups, query_string, start, end) = {
+        let mut app_ = app.lock().unwrap();
+        let (query_string, log_groups, (start, end)) = match tabs::query_of(&app_, tab) {
+            Some(query) => query,
+            None => return,
+        };
+        if tabs::results_mut(&mut app_, tab).map(|r| r.is_running()) == Some(true) {
+            app_.status_message =
+                StatusMessage::error("A query is already running in this tab, cancel it with c");
+            return;
+        }
+        let warnings = match lint(&query_string) {
+            Ok(warnings) => warnings,
+            Err(err) => {
+                app_.status_message = StatusMessage::error(&format!("Invalid query at {}", err));
+                return;
+            }
+        };
+        // Marked under the same lock as the check, so a second run waits for this one.
+        if let Some(log_results) = tabs::results_mut(&mut app_, tab) {
+            log_results.starting = true;
+            log_results.cancel_query = false;
+        }
+        app_.status_message = if warnings.is_empty() {
+            StatusMessage::info("Cloudwatch Insights query started")
+        } else {
+            StatusMessage::info(&format!(
+                "Cloudwatch Insights query started, {}",
+                warnings.join(", ")
+            ))
+        };
+        (log_groups, query_string, start, end)
+    };
+    let query = async move {
+        let policy = app.lock().unwrap().config.retry_policy();
+        let started = join_all(targets.iter().map(|target| {
+            with_retry(&policy, retry_status(app, "Starting the query", &policy), || {
+                target.backend.start_query(log_groups.clone(), query_string.clone(), start, end)
+            })
+        }))
+        .await;
+        let mut runs = vec![];
+        let mut errors = vec![];
+        for (index, res) in started.into_iter().enumerate() {
+            match res {
+                Ok(query_id) => runs.push((index, query_id)),
+                Err(err) => {
+                    error!("{:?}", err);
+                    errors.push(err);
+                }
+            }
+        }
+        if runs.is_empty() {
+            let mut app_ = app.lock().unwrap();
+            if let Some(log_results) = tabs::results_mut(&mut app_, tab) {
+                log_results.starting = false;
+            }
+            app_.status_message = match errors.first() {
+                Some(err) => StatusMessage::aws_error("Cloudwatch Insights query failed", err),
+                None => StatusMessage::error("Cloudwatch Insights query failed"),
+            };
+            return;
+        }
+        let slices = {
+            let mut app_ = app.lock().unwrap();
+            if let Some(log_results) = tabs::results_mut(&mut app_, tab) {
+                log_results.running_query_id = Some(runs[0].1.clone());
+                log_results.starting = false;
+                log_results.capped = false;
+                log_results.bin_column = bin_column(&query_string);
+                log_results.clear();
+                log_results.progress = Some(QueryProgress::start());
+            }
+            app_.config.time_slicing().filter(|_| can_slice(&query_string))
+        };
+
+        let run = QueryRun {
+            tab,
+            query: &query_string,
+            log_groups: &log_groups,
+            start,
+            end,
+            descending: !sorts_ascending(&query_string),
+            slices,
+        };
+        let partial = Mutex::new(vec![
+            QueryResults {
+                status: QueryStatus::Scheduled,
+                results: vec![],
+                statistics: None
+            };
+            targets.len()
+        ]);
+        let polled = join_all(runs.iter().map(|(index, query_id)| {
+            poll_query(app, &run, &targets[*index], query_id, *index, &partial)
+        }))
+        .await;
+
+        let statistics = total_statistics(&partial.into_inner().unwrap());
+        // Targets the query couldn't be started in count as failed requests.
+        let mut outcomes: Vec<(&Target, Option<QueryStatus>)> =
+            targets.iter().map(|target| (target, None)).collect();
+        let mut capped = false;
+        for ((index, _), outcome) in runs.iter().zip(polled) {
+            match outcome {
+                Ok((status, target_capped)) => {
+                    outcomes[*index].1 = Some(status);
+                    capped |= target_capped;
+                }
+                Err(err) => errors.push(err),
+            }
+        }
+        let status =
+            combined_status(outcomes.iter().map(|(_, s)| s.unwrap_or(QueryStatus::Unknown)));
+        let unsuccessful: Vec<String> = outcomes
+            .iter()
+            .filter(|(_, s)| {
+                *s != Some(QueryStatus::Complete) && *s != Some(QueryStatus::Cancelled)
+            })
+            .filter_map(|(target, _)| target.name())
+            .collect();
+        let location = if unsuccessful.is_empty() {
+            String::new()
+        } else {
+            format!(" in {}", unsuccessful.join(", "))
+        };
+
+        let mut app_ = app.lock().unwrap();
+        if let Some(err) = errors.first() {
+            app_.status_message = StatusMessage::aws_error(
+                &format!("Cloudwatch Insights query failed{}", location),
+                err,
+            );
+        } else {
+            match status {
+                QueryStatus::Complete => {
+                    app_.status_message = StatusMessage::info("Cloudwatch Insights query completed")
+                }
+                // The outcome of stopping the query is already in the status bar.
+                QueryStatus::Cancelled => {}
+                status => {
+                    app_.status_message = StatusMessage::error(&format!(
+                        "Cloudwatch Insights query ended with status {}{}",
+                        status, location
+                    ))
+                }
+            }
+        }
+        let mut records = 0;
+        if let Some(log_results) = tabs::results_mut(&mut app_, tab) {
+            log_results.running_query_id = None;
+            log_results.cancel_query = false;
+            log_results.capped = capped;
+            if let Some(progress) = log_results.progress.as_mut() {
+                progress.status = status;
+                progress.statistics = statistics;
+                progress.finish(status);
+            }
+            records = log_results.query_results.len();
+        }
+        let entry = HistoryEntry {
+            query: query_string,
+            log_groups,
+            start,
+            end,
+            status,
+            records,
+            statistics,
+        };
+        if let Err(err) = app_.history.record(entry) {
+            error!("Recording query history failed: {:?}", err);
+        }
+    };
+    join(query, run_histogram(app, backend, targets, tab)).await;
+}
+
+/// Counts the events the query of `tab` matches per bin over its time range, for the
+/// histogram above the results. Does nothing while the histogram is hidden.
+async fn run_histogram(
+    app: &Arc<Mutex<App>>,
+    backend: &dyn LogsBackend,
+    targets: &[Target<'_>],
+    tab: usize,
+) {
+    let single = [Target { source: None, backend }];
+    let targets = if targets.is_empty() { &single[..] } else { targets };
+    let (query_string, log_groups, (start, end)) = {
+        let mut app_ = app.lock().unwrap();
+        if !app_.show_histogram {
+            return;
+        }
+        let query = match tabs::query_of(&app_, tab) {
+            Some(query) => query,
+            None => return,
+        };
+        match tabs::results_mut(&mut app_, tab) {
+            Some(log_results) => log_results.histogram = None,
+            None => return,
+        }
+        query
+    };
+    // An invalid query is reported by the query itself.
+    if lint(&query_string).is_err() {
+        return;
+    }
+    let bin = bin_size(start, end);
+    let query_string = histogram_query(&query_string, bin);
+    let (poll_interval, policy) = {
+        let app_ = app.lock().unwrap();
+        (app_.config.poll_interval(), app_.config.retry_policy())
+    };
+    let counted = join_all(targets.iter().map(|target| async {
+        let on_retry = retry_status(app, "Starting the histogram query", &policy);
+        let query_id = with_retry(&policy, on_retry, || {
+            target.backend.start_query(log_groups.clone(), query_string.clone(), start, end)
+        })
+        .await?;
+        let on_retry = retry_status(app, "Polling the histogram query", &policy);
+        let res =
+            wait_for_results(target.backend, &query_id, poll_interval, &policy, on_retry, |_| {
+                is_wanted(app, tab)
+            })
+            .await?;
+        if !res.status.is_done() {
+            if let Err(err) = target.backend.stop_query(&query_id).await {
+                error!("{:?}", err);
+            }
+        }
+        Ok(res)
+    }))
+    .await;
+
+    let mut rows = vec![];
+    for res in counted {
+        match res {
+            Ok(res) => rows.extend(res.results),
+            Err(err) => {
+                error!("{:?}", err);
+                app.lock().unwrap().status_message =
+                    StatusMessage::aws_error("Histogram query failed", &err);
+                return;
+            }
+        }
+    }
+    if let Some(log_results) = tabs::results_mut(&mut app.lock().unwrap(), tab) {
+        log_results.histogram = Some(Histogram::from_results(&rows, start, end, bin));
+    }
+}
+
+/// Whether the results of a query in `tab` are still wanted, they aren't once the query is
+/// cancelled or the tab is closed.
+fn is_wanted(app: &Arc<Mutex<App>>, tab: usize) -> bool {
+    let mut app_ = app.lock().unwrap();
+    tabs::results_mut(&mut app_, tab).map(|r| !r.cancel_query).unwrap_or(false)
+}
+
+/// Polls a query started in `target` into the results of the tab, merging its rows with the
+/// other targets in `partial`. A query that hits [`MAX_ROWS`] is run again in smaller time
+/// windows when `run` says so.
+///
+/// Returns the final status and whether rows were cut off at [`MAX_ROWS`], or the error of
+/// the request that failed.
+async fn poll_query(
+    app: &Arc<Mutex<App>>,
+    run: &QueryRun<'_>,
+    target: &Target<'_>,
+    query_id: &str,
+    index: usize,
+    partial: &Mutex<Vec<QueryResults>>,
+) -> Result<(QueryStatus, bool), BackendError> {
+    let (poll_interval, policy) = {
+        let app_ = app.lock().unwrap();
+        (app_.config.poll_interval(), app_.config.retry_policy())
+    };
+    let on_retry = retry_status(app, "Polling the query", &policy);
+    let res = wait_for_results(target.backend, query_id, poll_interval, &policy, on_retry, |res| {
+        let mut partial = partial.lock().unwrap();
+        partial[index] = QueryResults {
+            status: res.status,
+            results: res.results.iter().cloned().map(|row| with_source(row, target)).collect(),
+            statistics: res.statistics,
+        };
+        show_partial(app, run, &partial)
+    })
+    .await;
+    match res {
+        Ok(res) if !res.status.is_done() => {
+            stop_query(app, target.backend, query_id).await;
+            partial.lock().unwrap()[index].status = QueryStatus::Cancelled;
+            Ok((QueryStatus::Cancelled, false))
+        }
+        Ok(res) if res.status == QueryStatus::Complete && res.results.len() >= MAX_ROWS => {
+            match (run.slices, split_window((run.start, run.end))) {
+                (Some(concurrency), Some(halves)) => {
+                    let sliced =
+                        run_slices(app, run, target, halves, concurrency, res.statistics).await;
+                    let (sliced, capped) = sliced.map_err(|err| {
+                        error!("{:?}", err);
+                        err
+                    })?;
+                    let status = sliced.status;
+                    let mut partial = partial.lock().unwrap();
+                    partial[index] = sliced;
+                    show_partial(app, run, &partial);
+                    Ok((status, capped))
+                }
+                _ => Ok((res.status, true)),
+            }
+        }
+        Ok(res) => Ok((res.status, false)),
+        Err(err) => {
+            error!("{:?}", err);
+            Err(err)
+        }
+    }
+}
+
+/// Shows the rows of all targets in the results of the tab, returns whether they are still
+/// wanted.
+fn show_partial(app: &Arc<Mutex<App>>, run: &QueryRun<'_>, partial: &[QueryResults]) -> bool {
+    let mut app_ = app.lock().unwrap();
+    let log_results = match tabs::results_mut(&mut app_, run.tab) {
+        Some(log_results) => log_results,
+        None => return false,
+    };
+    log_results.set_results(merge_results(partial, run.descending));
+    if let Some(progress) = log_results.progress.as_mut() {
+        progress.status = combined_status(partial.iter().map(|p| p.status));
+        progress.statistics = total_statistics(partial);
+    }
+    !log_results.cancel_query
+}
+
+/// Queries that can be split into time windows and stitched back together, the rows of
+/// `stats` don't add up, `dedup` would keep duplicates from different windows and a `limit`
+/// asks for fewer rows anyway. Windows are stitched in time order, so the rows must be sorted
+/// by `@timestamp`.
+fn can_slice(query: &str) -> bool {
+    !commands(query)
+        .iter()
+        .any(|c| ["stats", "limit", "dedup"].iter().any(|name| command_is(c, name)))
+        && sort_key(query).map(|(field, _)| field == "@timestamp").unwrap_or(true)
+}
+
+/// Splits a time window into two halves that don't overlap, both ends are included in a query.
+fn split_window((start, end): (i64, i64)) -> Option<[(i64, i64); 2]> {
+    if end <= start {
+        return None;
+    }
+    let middle = start + (end - start) / 2;
+    Some([(start, middle), (middle + 1, end)])
+}
+
+/// Runs the query of `run` in `target` again in time windows small enough to stay under
+/// [`MAX_ROWS`], starting with `halves` of its time range and running `concurrency` of them at
+/// once. Windows that still hit the limit are split again until they are down to a second.
+///
+/// Returns the rows of all windows in the order of the query with the statistics of all of them
+/// and the first run, whose `statistics` are given, and whether any window was still cut off.
+async fn run_slices(
+    app: &Arc<Mutex<App>>,
+    run: &QueryRun<'_>,
+    target: &Target<'_>,
+    halves: [(i64, i64); 2],
+    concurrency: usize,
+    statistics: Option<QueryStatistics>,
+) -> BackendResult<(QueryResults, bool)> {
+    let mut pending = halves.to_vec();
+    let mut running = FuturesUnordered::new();
+    let mut done: Vec<((i64, i64), QueryResults)> = vec![];
+    let mut windows = 2;
+    let mut capped = false;
+    let mut cancelled = false;
+    loop {
+        while running.len() < concurrency && !cancelled {
+            match pending.pop() {
+                Some(window) => running.push(run_window(app, run, target, window)),
+                None => break,
+            }
+        }
+        let (window, res) = match running.next().await {
+            Some(finished) => finished,
+            None => break,
+        };
+        let res = res?;
+        match split_window(window) {
+            _ if !res.status.is_done() => cancelled = true,
+            Some(halves) if res.results.len() >= MAX_ROWS => {
+                pending.extend(halves);
+                windows += 1;
+            }
+            _ => {
+                capped |= res.results.len() >= MAX_ROWS;
+                done.push((window, res));
+            }
+        }
+        if !cancelled {
+            app.lock().unwrap().status_message = StatusMessage::info(&format!(
+                "Cloudwatch Insights query hit {} rows, fetched {} of {} time windows",
+                MAX_ROWS,
+                done.len(),
+                windows
+            ));
+        }
+    }
+
+    done.sort_by_key(|(window, _)| window.0);
+    if run.descending {
+        done.reverse();
+    }
+    let mut results: Vec<QueryResults> = done.into_iter().map(|(_, res)| res).collect();
+    let status = if cancelled {
+        QueryStatus::Cancelled
+    } else {
+        combined_status(results.iter().map(|r| r.status))
+    };
+    // The first run scanned the whole time range, that counts too.
+    results.push(QueryResults { status, results: vec![], statistics });
+    let statistics = Some(total_statistics(&results));
+    let rows = results
+        .into_iter()
+        .flat_map(|res| res.results)
+        .map(|row| with_source(row, target))
+        .collect();
+    Ok((QueryResults { status, results: rows, statistics }, capped))
+}
+
+/// Runs the query of `run` in `target` for one time window, stopping it when it's no longer
+/// wanted.
+async fn run_window(
+    app: &Arc<Mutex<App>>,
+    run: &QueryRun<'_>,
+    target: &Target<'_>,
+    (start, end): (i64, i64),
+) -> ((i64, i64), BackendResult<QueryResults>) {
+    let (poll_interval, policy) = {
+        let app_ = app.lock().unwrap();
+        (app_.config.poll_interval(), app_.config.retry_policy())
+    };
+    let res = async {
+        let on_retry = retry_status(app, "Starting the query", &policy);
+        let query_id = with_retry(&policy, on_retry, || {
+            target.backend.start_query(run.log_groups.to_vec(), run.query.to_string(), start, end)
+        })
+        .await?;
+        let on_retry = retry_status(app, "Polling the query", &policy);
+        let res =
+            wait_for_results(target.backend, &query_id, poll_interval, &policy, on_retry, |_| {
+                is_wanted(app, run.tab)
+            })
+            .await?;
+        if !res.status.is_done() {
+            stop_query(app, target.backend, &query_id).await;
+        }
+        Ok(res)
+    }
+    .await;
+    ((start, end), res)
+}
+
+fn with_source(mut row: ResultRow, target: &Target<'_>) -> ResultRow {
+    if let Some((region, profile)) = &target.source {
+        row.fields.insert(0, ("@profile".to_string(), profile.clone()));
+        row.fields.insert(0, ("@region".to_string(), region.clone()));
+    }
+    row
+}
+
+/// The field the last `sort` of `query` sorts by first and whether it sorts ascending, `None`
+/// without a `sort`.
+fn sort_key(query: &str) -> Option<(String, bool)> {
+    let sort = commands(query).into_iter().rev().find(|c| command_is(c, "sort"))?;
+    let (_, keys) = sort.split_once(char::is_whitespace)?;
+    let mut key = keys.split(',').next()?.split_whitespace();
+    let field = key.next()?.to_string();
+    let ascending = key.next().map(|order| order.eq_ignore_ascii_case("asc")).unwrap_or(false);
+    Some((field, ascending))
+}
+
+/// Whether the query sorts by `@timestamp` ascending, Insights returns newest first otherwise.
+fn sorts_ascending(query: &str) -> bool {
+    sort_key(query) == Some(("@timestamp".to_string(), true))
+}
+
+/// Rows of all targets in one list, rows from different targets are interleaved by timestamp.
+fn merge_results(partial: &[QueryResults], descending: bool) -> Vec<ResultRow> {
+    let mut rows: Vec<ResultRow> = partial.iter().flat_map(|p| p.results.iter().cloned()).collect();
+    if partial.len() > 1 {
+        rows.sort_by(|a, b| {
+            let ordering = a.get("@timestamp").cmp(&b.get("@timestamp"));
+            if descending {
+                ordering.reverse()
+            } else {
+                ordering
+            }
+        });
+    }
+    rows
+}
+
+/// A single status for queries running in several targets, the first one that didn't complete
+/// wins unless any of them was cancelled.
+fn combined_status(statuses: impl Iterator<Item = QueryStatus>) -> QueryStatus {
+    let statuses: Vec<QueryStatus> = statuses.collect();
+    if statuses.contains(&QueryStatus::Cancelled) {
+        return QueryStatus::Cancelled;
+    }
+    if let Some(status) = statuses.iter().find(|s| !s.is_done()) {
+        return *status;
+    }
+    statuses.into_iter().find(|s| *s != QueryStatus::Complete).unwrap_or(QueryStatus::Complete)
+}
+
+fn total_statistics(partial: &[QueryResults]) -> QueryStatistics {
+    partial.iter().filter_map(|p| p.statistics).fold(QueryStatistics::default(), |total, s| {
+        QueryStatistics {
+            records_matched: total.records_matched + s.records_matched,
+            records_scanned: total.records_scanned + s.records_scanned,
+            bytes_scanned: total.bytes_scanned + s.bytes_scanned,
+        }
+    })
+}
+
+/// Polls the results of a started query every `poll_interval` until it is no longer running.
+///
+/// Every response is handed to `on_poll`, which can stop the polling early by returning
+/// `false`. Throttled polls are retried according to `policy`, calling `on_retry` before every
+/// retry. Returns the last response received.
+pub(crate) async fn wait_for_results(
+    backend: &dyn LogsBackend,
+    query_id: &str,
+    poll_interval: Duration,
+    policy: &RetryPolicy,
+    mut on_retry: impl FnMut(u32, &BackendError, Duration),
+    mut on_poll: impl FnMut(&QueryResults) -> bool,
+) -> BackendResult<QueryResults> {
+    loop {
+        let res = with_retry(policy, &mut on_retry, || backend.get_query_results(query_id)).await?;
+        info!("query: {:?}", res);
+        if !on_poll(&res) || res.status.is_done() {
+            return Ok(res);
+        }
+        tokio::time::sleep(poll_interval).await;
+    }
+}
+
+/// Shows retries of `what` in the status bar.
+fn retry_status<'a>(
+    app: &'a Arc<Mutex<App>>,
+    what: &'a str,
+    policy: &'a RetryPolicy,
+) -> impl FnMut(u32, &BackendError, Duration) + 'a {
+    move |attempt, err, delay| {
+        app.lock().unwrap().status_message =
+            StatusMessage::info(&retry_message(what, policy, attempt, err, delay));
+    }
+}
+
+/// Stops a running query, leaving whatever results were already fetched in place.
+async fn stop_query(app: &Arc<Mutex<App>>, backend: &dyn LogsBackend, query_id: &str) {
+    let res = backend.stop_query(query_id).await;
+    let mut app_ = app.lock().unwrap();
+    match res {
+        Ok(_) => {
+            app_.status_message = StatusMessage::info("Cloudwatch Insights query cancelled");
+        }
+        Err(err) => {
+            error!("{:?}", err);
+            app_.status_message =
+                StatusMessage::aws_error("Cancelling Cloudwatch Insights query failed", &err);
+        }
+    }
+}
+
+async fn get_log_record(
+    app: &Arc<Mutex<App>>,
+    backend: &dyn LogsBackend,
+    targets: &[Target<'_>],
+    ptr: &str,
+) {
+    // A pointer is only valid in the region and profile the row came from.
+    let backend = {
+        let mut app_ = app.lock().unwrap();
+        app_.status_message = StatusMessage::info("Log record request started");
+        app_.log_results
+            .query_results
+            .iter()
+            .find(|row| row.get("@ptr") == Some(ptr))
+            .and_then(|row| {
+                targets.iter().find(|target| match &target.source {
+                    Some((region, profile)) => {
+                        row.get("@region") == Some(region) && row.get("@profile") == Some(profile)
+                    }
+                    None => false,
+                })
+            })
+            .map(|target| target.backend)
+            .unwrap_or(backend)
+    };
+    let res = backend.get_log_record(ptr).await;
+    let mut app_ = app.lock().unwrap();
+    match res {
+        Ok(record) => {
+            let mut fields: Vec<(String, String)> = record.into_iter().collect();
+            fields.sort();
+            app_.log_record.fields = fields;
+            app_.status_message = StatusMessage::info("Log record request completed");
+        }
+        Err(err) => {
+            error!("{:?}", err);
+            app_.status_message = StatusMessage::aws_error("Log record request failed", &err);
+        }
+    }
+}
+
+/// Where tailing a log group continues from on the next poll.
+struct TailCursor {
+    log_group: String,
+    /// Milliseconds since the epoch.
+    start: i64,
+    /// Events at exactly `start` that were already shown, they are returned again.
+    seen: HashSet<String>,
+}
+
+/// Polls the log groups of the tail view for new events until the view is closed.
+async fn tail(app: &Arc<Mutex<App>>, backend: &dyn LogsBackend) {
+    let mut cursors = vec![];
+    let mut filter_pattern = None;
+    loop {
+        let (poll_interval, paused) = {
+            let mut app_ = app.lock().unwrap();
+            if !app_.tail.running {
+                return;
+            }
+            if app_.tail.restart {
+                app_.tail.restart = false;
+                app_.tail.events.clear();
+                let start = now() * 1000 - TAIL_BACKFILL_MS;
+                cursors = app_
+                    .tail
+                    .log_groups
+                    .iter()
+                    .map(|log_group| TailCursor {
+                        log_group: log_group.clone(),
+                        start,
+                        seen: HashSet::new(),
+                    })
+                    .collect();
+                let filter = app_.tail.filter.trim();
+                filter_pattern = if filter.is_empty() { None } else { Some(filter.to_string()) };
+                app_.status_message = StatusMessage::info("Tail started");
+            }
+            (app_.config.poll_interval(), app_.tail.paused)
+        };
+        if !paused {
+            let mut events = vec![];
+            for cursor in cursors.iter_mut() {
+                match poll_log_group(backend, cursor, filter_pattern.clone()).await {
+                    Ok(new_events) => {
+                        events.extend(new_events.into_iter().map(|e| (cursor.log_group.clone(), e)))
+                    }
+                    Err(err) => {
+                        error!("{:?}", err);
+                        let mut app_ = app.lock().unwrap();
+                        app_.tail.running = false;
+                        app_.status_message = StatusMessage::aws_error("Tail request failed", &err);
+                        return;
+                    }
+                }
+            }
+            events.sort_by_key(|(_, e)| e.timestamp);
+            let mut app_ = app.lock().unwrap();
+            app_.tail.push(events.into_iter().map(|(log_group, e)| to_row(&log_group, e)));
+        }
+        tokio::time::sleep(poll_interval).await;
+    }
+}
+
+/// Events of one log group that weren't returned by earlier polls, oldest first.
+async fn poll_log_group(
+    backend: &dyn LogsBackend,
+    cursor: &mut TailCursor,
+    filter_pattern: Option<String>,
+) -> BackendResult<Vec<LogEvent>> {
+    let mut events = vec![];
+    let mut next_token = None;
+    loop {
+        let page = backend
+            .filter_log_events(&cursor.log_group, filter_pattern.clone(), cursor.start, next_token)
+            .await?;
+        events.extend(page.events.into_iter().filter(|e| !cursor.seen.contains(&e.event_id)));
+        next_token = page.next_token;
+        if next_token.is_none() {
+            break;
+        }
+    }
+    if let Some(latest) = events.iter().map(|e| e.timestamp).max() {
+        if latest > cursor.start {
+            cursor.start = latest;
+            cursor.seen.clear();
+        }
+        cursor.seen.extend(
+            events.iter().filter(|e| e.timestamp == cursor.start).map(|e| e.event_id.clone()),
+        );
+    }
+    events.sort_by_key(|e| e.timestamp);
+    Ok(events)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc::channel;
+
+    use super::{backend::QueryStatus, fake::FakeBackend, *};
+    use crate::{overview::ResultRow, time_select::TimeSelector};
+
+    fn row(fields: &[(&str, &str)]) -> ResultRow {
+        ResultRow { fields: fields.iter().map(|(f, v)| (f.to_string(), v.to_string())).collect() }
+    }
+
+    fn results(status: QueryStatus, rows: Vec<ResultRow>) -> QueryResults {
+        QueryResults { status, results: rows, statistics: None }
+    }
+
+    fn serve_all(app: &Arc<Mutex<App>>, backend: &FakeBackend, reqs: Vec<AwsReq>) {
+        let (tx, rx) = channel();
+        reqs.into_iter().for_each(|req| tx.send(req).unwrap());
+        drop(tx);
+        let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+        rt.block_on(serve(app, &rx, backend, &[]));
+    }
+
+    #[test]
+    fn only_queries_keeping_every_row_in_time_order_are_sliced() {
+        let cases = [
+            ("fields @timestamp, @message", true),
+            ("fields @message | sort @timestamp asc", true),
+            ("fields @message | SORT @timestamp DESC, @message", true),
+            ("fields @message | filter @message like /stats|limit/ # limit 10", true),
+            ("stats count(*) by bin(5m)", false),
+            ("fields @message | limit 20", false),
+            ("fields @message | dedup @logStream", false),
+            ("fields @message | sort @message asc", false),
+            ("fields @message | sort @message | sort @timestamp", true),
+        ];
+        for (query, sliced) in cases {
+            assert_eq!(can_slice(query), sliced, "{}", query);
+        }
+    }
+
+    #[test]
+    fn only_sorting_by_timestamp_ascending_is_ascending() {
+        let cases = [
+            ("fields @message", false),
+            ("fields @message | sort @timestamp asc", true),
+            ("fields @message\n| Sort   @timestamp   ASC, @message desc", true),
+            ("fields @message | sort @timestamp desc", false),
+            ("fields @message | sort @timestamp", false),
+            ("fields @message | sort @message asc, @timestamp asc", false),
+            ("fields @message # sort @timestamp asc", false),
+            ("fields @message | sort @timestamp asc | sort @timestamp desc", false),
+        ];
+        for (query, ascending) in cases {
+            assert_eq!(sorts_ascending(query), ascending, "{}", query);
+        }
+    }
+
+    #[test]
+    fn list_log_groups_follows_pagination() {
+        let app = Arc::new(Mutex::new(App::default()));
+        let backend =
+            FakeBackend::default().with_log_group_pages(vec![vec!["/a", "/b"], vec!["/c"]]);
+
+        serve_all(&app, &backend, vec![AwsReq::ListLogGroups]);
+
+        let app = app.lock().unwrap();
+        assert_eq!(app.log_groups.log_groups, vec!["/a", "/b", "/c"]);
+        assert_eq!(app.status_message.text(), "Log groups request completed");
+    }
+
+    #[test]
+    fn list_log_groups_replaces_previous_list() {
+        let app = Arc::new(Mutex::new(App::default()));
+        let backend = FakeBackend::default().with_log_group_pages(vec![vec!["/a"]]);
+
+        serve_all(&app, &backend, vec![AwsReq::ListLogGroups, AwsReq::ListLogGroups]);
+
+        assert_eq!(app.lock().unwrap().log_groups.log_groups, vec!["/a"]);
+    }
+
+    #[test]
+    fn run_query_polls_until_complete() {
+        let app = Arc::new(Mutex::new(App::default()));
+        let backend = FakeBackend::default().with_query_results(vec![
+            results(QueryStatus::Scheduled, vec![]),
+            results(QueryStatus::Running, vec![row(&[("@message", "first")])]),
+            results(
+                QueryStatus::Complete,
+                vec![row(&[("@message", "first")]), row(&[("@message", "second")])],
+            ),
+        ]);
+
+        serve_all(&app, &backend, vec![AwsReq::RunQuery(0)]);
+
+        let app = app.lock().unwrap();
+        assert_eq!(app.log_results.query_results.len(), 2);
+        assert_eq!(app.log_results.query_results[1].get("@message"), Some("second"));
+        assert_eq!(app.status_message.text(), "Cloudwatch Insights query completed");
+        assert_eq!(*backend.started_queries.lock().unwrap(), vec![app.query.clone()]);
+        assert_eq!(app.history.entries.len(), 1);
+        assert_eq!(app.history.entries[0].status, QueryStatus::Complete);
+        assert_eq!(app.history.entries[0].records, 2);
+    }
+
+    #[test]
+    fn run_query_stops_polling_on_failure() {
+        let app = Arc::new(Mutex::new(App::default()));
+        let backend = FakeBackend::default().with_query_results(vec![
+            results(QueryStatus::Running, vec![]),
+            results(QueryStatus::Failed, vec![]),
+            results(QueryStatus::Complete, vec![row(&[("@message", "never")])]),
+        ]);
+
+        serve_all(&app, &backend, vec![AwsReq::RunQuery(0)]);
+
+        let app = app.lock().unwrap();
+        assert!(app.log_results.query_results.is_empty());
+        assert_eq!(app.log_results.progress.as_ref().unwrap().status, QueryStatus::Failed);
+        assert_eq!(app.status_message.text(), "Cloudwatch Insights query ended with status Failed");
+    }
+
+    #[test]
+    fn get_log_record_sorts_fields() {
+        let app = Arc::new(Mutex::new(App::default()));
+        let backend = FakeBackend::default().with_log_record(
+            "ptr-1",
+            vec![("@message", "hello"), ("level", "info"), ("@logStream", "stream")],
+        );
+
+        serve_all(&app, &backend, vec![AwsReq::GetLogRecord("ptr-1".to_string())]);
+
+        let app = app.lock().unwrap();
+        let fields: Vec<&str> = app.log_record.fields.iter().map(|(f, _)| f.as_str()).collect();
+        assert_eq!(fields, vec!["@logStream", "@message", "level"]);
+        assert_eq!(app.status_message.text(), "Log record request completed");
+    }
+
+    #[test]
+    fn cancel_stops_running_query_and_keeps_partial_results() {
+        let app = Arc::new(Mutex::new(App::default()));
+        let backend = FakeBackend::default().with_query_results(vec![results(
+            QueryStatus::Running,
+            vec![row(&[("@message", "partial")])],
+        )]);
+        let app_r = app.clone();
+        let canceller = std::thread::spawn(move || loop {
+            {
+                let mut app_ = app_r.lock().unwrap();
+                let log_results = &mut app_.log_results;
+                if log_results.running_query_id.is_some() && !log_results.query_results.is_empty() {
+                    log_results.cancel_query = true;
+                    return;
+                }
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        });
+
+        serve_all(&app, &backend, vec![AwsReq::RunQuery(0), AwsReq::ListLogGroups]);
+        canceller.join().unwrap();
+
+        assert_eq!(*backend.stopped_queries.lock().unwrap(), vec!["query-1"]);
+        let app = app.lock().unwrap();
+        assert_eq!(app.log_results.query_results[0].get("@message"), Some("partial"));
+        assert_eq!(app.log_results.running_query_id, None);
+        assert_eq!(app.history.entries[0].status, QueryStatus::Cancelled);
+        assert_eq!(app.status_message.text(), "Cloudwatch Insights query cancelled");
+    }
+
+    #[test]
+    fn queries_of_tabs_run_into_their_own_results() {
+        let (tx, _rx) = channel();
+        let mut app = App::default();
+        crate::tabs::open(&mut app);
+        app.query = "fields @timestamp, @message | limit 1".to_string();
+        let app = Arc::new(Mutex::new(app));
+        let backend = FakeBackend::default().with_query_results(vec![
+            results(QueryStatus::Running, vec![]),
+            results(QueryStatus::Complete, vec![row(&[("@message", "done")])]),
+        ]);
+
+        serve_all(&app, &backend, vec![AwsReq::RunQuery(0), AwsReq::RunQuery(1)]);
+
+        let mut app = app.lock().unwrap();
+        assert_eq!(backend.started_queries.lock().unwrap().len(), 2);
+        assert_eq!(backend.started_queries.lock().unwrap()[1], app.query);
+        assert_eq!(app.log_results.query_results.len(), 1);
+        assert_eq!(app.log_results.progress.as_ref().unwrap().status, QueryStatus::Complete);
+        crate::tabs::switch(&mut app, 0, &tx);
+        assert_eq!(app.query, crate::DEFAULT_QUERY);
+        assert_eq!(app.log_results.query_results.len(), 1);
+        assert_eq!(app.history.entries.len(), 2);
+    }
+
+    #[test]
+    fn binned_stats_results_are_charted() {
+        let app = Arc::new(Mutex::new(App {
+            query: "stats count(*), avg(@duration) by bin(1m)".to_string(),
+            ..App::default()
+        }));
+        let backend = FakeBackend::default().with_query_results(vec![results(
+            QueryStatus::Complete,
+            vec![
+                row(&[
+                    ("bin(1m)", "2021-11-01 10:01:00.000"),
+                    ("count(*)", "3"),
+                    ("avg(@duration)", "1.5"),
+                ]),
+                row(&[
+                    ("bin(1m)", "2021-11-01 10:00:00.000"),
+                    ("count(*)", "7"),
+                    ("avg(@duration)", "2"),
+                ]),
+            ],
+        )]);
+
+        serve_all(&app, &backend, vec![AwsReq::RunQuery(0)]);
+
+        let app = app.lock().unwrap();
+        let chart = app.log_results.chart.as_ref().unwrap();
+        let series: Vec<_> = chart.series.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(series, vec!["count(*)", "avg(@duration)"]);
+        let start = 1_635_760_800.0;
+        assert_eq!(chart.series[0].points, vec![(start, 7.0), (start + 60.0, 3.0)]);
+        assert_eq!(chart.series[1].points, vec![(start, 2.0), (start + 60.0, 1.5)]);
+    }
+
+    #[test]
+    fn histogram_counts_matching_events_per_bin() {
+        let start = 1_635_760_800;
+        let app = Arc::new(Mutex::new(App {
+            query: "fields @timestamp, @message\n| filter @message like /error/\n| limit 5"
+                .to_string(),
+            time_selector: TimeSelector::from_timestamps(start, start + 3600).unwrap(),
+            show_histogram: true,
+            ..App::default()
+        }));
+        let backend = FakeBackend::default().with_query_results(vec![results(
+            QueryStatus::Complete,
+            vec![
+                row(&[("bin(1m)", "2021-11-01 10:02:00.000"), ("count(*)", "4")]),
+                row(&[("bin(1m)", "2021-11-01 10:00:00.000"), ("count(*)", "7")]),
+            ],
+        )]);
+
+        serve_all(&app, &backend, vec![AwsReq::RunQuery(0)]);
+
+        assert!(backend.started_queries.lock().unwrap().contains(
+            &"fields @timestamp, @message | filter @message like /error/ | stats count(*) by bin(1m)"
+                .to_string()
+        ));
+        let app = app.lock().unwrap();
+        let histogram = app.log_results.histogram.as_ref().unwrap();
+        assert_eq!(histogram.bin, 60);
+        assert_eq!(histogram.buckets.len(), 60);
+        assert_eq!(histogram.buckets[..3], [(start, 7), (start + 60, 0), (start + 120, 4)]);
+    }
+
+    fn fan_out(app: &Arc<Mutex<App>>, targets: Vec<(&str, &FakeBackend)>) {
+        let (tx, rx) = channel();
+        tx.send(AwsReq::RunQuery(0)).unwrap();
+        drop(tx);
+        let targets: Vec<Target> = targets
+            .into_iter()
+            .map(|(region, backend)| Target {
+                source: Some((region.to_string(), "default".to_string())),
+                backend,
+            })
+            .collect();
+        let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+        rt.block_on(serve(app, &rx, &FakeBackend::default(), &targets));
+    }
+
+    #[test]
+    fn fan_out_merges_results_by_timestamp() {
+        let app = Arc::new(Mutex::new(App::default()));
+        let us = FakeBackend::default().with_query_results(vec![results(
+            QueryStatus::Complete,
+            vec![
+                row(&[("@timestamp", "2021-11-01 10:00:02.000"), ("@message", "a")]),
+                row(&[("@timestamp", "2021-11-01 10:00:00.000"), ("@message", "c")]),
+            ],
+        )]);
+        let eu = FakeBackend::default().with_query_results(vec![results(
+            QueryStatus::Complete,
+            vec![row(&[("@timestamp", "2021-11-01 10:00:01.000"), ("@message", "b")])],
+        )]);
+
+        fan_out(&app, vec![("us-east-1", &us), ("eu-west-1", &eu)]);
+
+        let app = app.lock().unwrap();
+        let messages: Vec<_> =
+            app.log_results.query_results.iter().map(|r| r.get("@message").unwrap()).collect();
+        assert_eq!(messages, vec!["a", "b", "c"]);
+        assert_eq!(app.log_results.query_results[1].get("@region"), Some("eu-west-1"));
+        assert_eq!(app.log_results.query_results[1].get("@profile"), Some("default"));
+        assert_eq!(app.log_results.columns[..2], ["@region", "@profile"]);
+        assert_eq!(app.status_message.text(), "Cloudwatch Insights query completed");
+        assert_eq!(app.history.entries[0].records, 3);
+    }
+
+    #[test]
+    fn fan_out_reports_failed_targets() {
+        let app = Arc::new(Mutex::new(App::default()));
+        let us = FakeBackend::default().with_query_results(vec![results(
+            QueryStatus::Complete,
+            vec![row(&[("@message", "kept")])],
+        )]);
+        let eu =
+            FakeBackend::default().with_query_results(vec![results(QueryStatus::Failed, vec![])]);
+
+        fan_out(&app, vec![("us-east-1", &us), ("eu-west-1", &eu)]);
+
+        let app = app.lock().unwrap();
+        assert_eq!(app.log_results.query_results[0].get("@message"), Some("kept"));
+        assert_eq!(
+            app.status_message.text(),
+            "Cloudwatch Insights query ended with status Failed in eu-west-1/default"
+        );
+        assert_eq!(app.history.entries[0].status, QueryStatus::Failed);
+    }
+
+    #[test]
+    fn failed_request_is_classified_with_detail() {
+        let app = app_with_retries(0);
+        let backend = FakeBackend::default().with_error("ThrottlingException", "Rate exceeded");
+
+        serve_all(&app, &backend, vec![AwsReq::ListLogGroups]);
+
+        let app = app.lock().unwrap();
+        assert_eq!(
+            app.status_message.text(),
+            "Log groups request failed: throttled by AWS, try again in a moment"
+        );
+        assert!(app.status_message.has_detail());
+    }
+
+    #[test]
+    fn worker_keeps_serving_after_failed_requests() {
+        let app = Arc::new(Mutex::new(App::default()));
+        // No canned query results, so polling the query fails.
+        let backend = FakeBackend::default().with_log_group_pages(vec![vec!["/a"]]);
+
+        serve_all(
+            &app,
+            &backend,
+            vec![
+                AwsReq::RunQuery(0),
+                AwsReq::GetLogRecord("missing".to_string()),
+                AwsReq::ListLogGroups,
+            ],
+        );
+
+        let app = app.lock().unwrap();
+        assert_eq!(app.log_groups.log_groups, vec!["/a"]);
+        assert!(app.log_results.running_query_id.is_none());
+        assert_eq!(app.history.entries[0].status, QueryStatus::Unknown);
+        // The other requests are done before the query is first polled, so it fails last.
+        assert_eq!(
+            app.status_message.text(),
+            "Cloudwatch Insights query failed: the request failed"
+        );
+    }
+
+    #[test]
+    fn a_second_run_waits_for_the_query_being_started() {
+        let app = Arc::new(Mutex::new(App::default()));
+        let backend = FakeBackend::default()
+            .with_query_results(vec![results(QueryStatus::Complete, vec![row(&[])])])
+            .with_start_delay(Duration::from_millis(200));
+
+        serve_all(&app, &backend, vec![AwsReq::RunQuery(0), AwsReq::RunQuery(0)]);
+
+        assert_eq!(backend.started_queries.lock().unwrap().len(), 1);
+        let app = app.lock().unwrap();
+        assert!(!app.log_results.is_running());
+        assert_eq!(app.history.entries.len(), 1);
+    }
+
+    #[test]
+    fn queries_run_while_tailing() {
+        let app = Arc::new(Mutex::new(App {
+            config: toml::from_str("poll_interval_ms = 10").unwrap(),
+            ..App::default()
+        }));
+        {
+            let mut app_ = app.lock().unwrap();
+            app_.tail.log_groups = vec!["/a".to_string()];
+            app_.tail.running = true;
+            app_.tail.restart = true;
+        }
+        let backend = FakeBackend::default()
+            .with_log_events("/a", vec![])
+            .with_query_results(vec![results(QueryStatus::Complete, vec![row(&[])])]);
+        let app_r = app.clone();
+        let closer = std::thread::spawn(move || loop {
+            {
+                let mut app_ = app_r.lock().unwrap();
+                if app_.history.entries.len() == 1 {
+                    let tailing = app_.tail.running;
+                    app_.tail.running = false;
+                    return tailing;
+                }
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        });
+
+        serve_all(&app, &backend, vec![AwsReq::Tail, AwsReq::RunQuery(0)]);
+
+        assert!(closer.join().unwrap(), "the query should complete while the tail runs");
+        assert_eq!(app.lock().unwrap().history.entries[0].status, QueryStatus::Complete);
+    }
+
+    #[test]
+    fn malformed_query_results_are_kept() {
+        let app = Arc::new(Mutex::new(App::default()));
+        let backend = FakeBackend::default().with_query_results(vec![results(
+            QueryStatus::Unknown,
+            vec![
+                row(&[]),
+                row(&[("@ptr", "ptr-1")]),
+                row(&[("@message", "first"), ("@message", "second")]),
+            ],
+        )]);
+
+        serve_all(&app, &backend, vec![AwsReq::RunQuery(0)]);
+        serve_all(&app, &backend, vec![AwsReq::GetLogRecord("ptr-1".into())]);
+
+        let app = app.lock().unwrap();
+        assert_eq!(app.log_results.query_results.len(), 3);
+        assert_eq!(app.log_results.columns, vec!["@message"]);
+        assert_eq!(app.log_results.progress.as_ref().unwrap().status, QueryStatus::Unknown);
+        assert_eq!(app.status_message.text(), "Log record request failed: the request failed");
+        assert!(app.log_record.fields.is_empty());
+    }
+
+    #[test]
+    fn queries_hitting_the_row_limit_are_sliced_into_time_windows() {
+        let start = 1_635_760_800;
+        let app = Arc::new(Mutex::new(App {
+            config: toml::from_str("time_slicing = true\nslice_concurrency = 2").unwrap(),
+            time_selector: TimeSelector::from_timestamps(start, start + 24_999).unwrap(),
+            ..App::default()
+        }));
+        let backend = FakeBackend::default().with_query_events((start..start + 25_000).collect());
+
+        serve_all(&app, &backend, vec![AwsReq::RunQuery(0)]);
+
+        // The whole range and both halves hit the limit, the quarters don't.
+        assert_eq!(backend.query_windows.lock().unwrap().len(), 7);
+        let app = app.lock().unwrap();
+        let rows = &app.log_results.query_results;
+        assert_eq!(rows.len(), 25_000);
+        let messages: Vec<i64> =
+            rows.iter().map(|r| r.get("@message").unwrap().parse().unwrap()).collect();
+        assert_eq!(messages, (start..start + 25_000).rev().collect::<Vec<_>>());
+        assert!(!app.log_results.capped);
+    }
+
+    #[test]
+    fn queries_hitting_the_row_limit_are_capped_without_slicing() {
+        let start = 1_635_760_800;
+        let app = Arc::new(Mutex::new(App {
+            time_selector: TimeSelector::from_timestamps(start, start + 24_999).unwrap(),
+            ..App::default()
+        }));
+        let backend = FakeBackend::default().with_query_events((start..start + 25_000).collect());
+
+        serve_all(&app, &backend, vec![AwsReq::RunQuery(0)]);
+
+        assert_eq!(backend.query_windows.lock().unwrap().len(), 1);
+        let app = app.lock().unwrap();
+        assert_eq!(app.log_results.query_results.len(), MAX_ROWS);
+        assert!(app.log_results.capped);
+    }
+
+    fn app_with_retries(max_retries: u32) -> Arc<Mutex<App>> {
+        let config = format!("max_retries = {}\nretry_base_delay_ms = 1", max_retries);
+        Arc::new(Mutex::new(App { config: toml::from_str(&config).unwrap(), ..App::default() }))
+    }
+
+    #[test]
+    fn throttled_requests_are_retried() {
+        let app = app_with_retries(3);
+        let backend = FakeBackend::default()
+            .with_log_group_pages(vec![vec!["/a"], vec!["/b"]])
+            .with_query_results(vec![results(QueryStatus::Complete, vec![row(&[])])])
+            .with_throttled_requests(3);
+
+        serve_all(&app, &backend, vec![AwsReq::ListLogGroups]);
+        assert_eq!(app.lock().unwrap().log_groups.log_groups, vec!["/a", "/b"]);
+
+        let backend = backend.with_throttled_requests(2);
+        serve_all(&app, &backend, vec![AwsReq::RunQuery(0)]);
+        let app = app.lock().unwrap();
+        assert_eq!(app.status_message.text(), "Cloudwatch Insights query completed");
+        assert_eq!(app.log_results.query_results.len(), 1);
+    }
+
+    #[test]
+    fn throttled_requests_give_up_after_max_retries() {
+        let app = app_with_retries(2);
+        let backend = FakeBackend::default()
+            .with_log_group_pages(vec![vec!["/a"]])
+            .with_throttled_requests(3);
+
+        serve_all(&app, &backend, vec![AwsReq::ListLogGroups]);
+
+        let app = app.lock().unwrap();
+        assert!(app.log_groups.log_groups.is_empty());
+        assert_eq!(
+            app.status_message.text(),
+            "Log groups request failed: throttled by AWS, try again in a moment"
+        );
+    }
+
+    #[test]
+    fn tail_appends_new_events_once() {
+        let app = Arc::new(Mutex::new(App::default()));
+        {
+            let mut app_ = app.lock().unwrap();
+            app_.tail.log_groups = vec!["/a".to_string()];
+            app_.tail.filter = "error".to_string();
+            app_.tail.running = true;
+            app_.tail.restart = true;
+        }
+        let now = now() * 1000;
+        let backend = FakeBackend::default().with_log_events(
+            "/a",
+            vec![
+                (now - TAIL_BACKFILL_MS - 1000, "error too old"),
+                (now - 10, "error second"),
+                (now - 20, "error first"),
+                (now - 5, "info filtered"),
+            ],
+        );
+        let app_r = app.clone();
+        let stopper = std::thread::spawn(move || loop {
+            if !app_r.lock().unwrap().tail.events.is_empty() {
+                // Let the worker poll the same events again before stopping it.
+                std::thread::sleep(Duration::from_millis(700));
+                app_r.lock().unwrap().tail.running = false;
+                return;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        });
+
+        serve_all(&app, &backend, vec![AwsReq::Tail]);
+        stopper.join().unwrap();
+
+        let app = app.lock().unwrap();
+        let messages: Vec<_> = app.tail.events.iter().map(|r| r.get("@message").unwrap()).collect();
+        assert_eq!(messages, vec!["error first", "error second"]);
+    }
+
+    #[test]
+    fn log_group_fields_are_fetched_once_and_merged() {
+        let app = Arc::new(Mutex::new(App::default()));
+        app.lock().unwrap().log_groups.selected_log_groups = vec!["/a".to_string()];
+        let backend = FakeBackend::default()
+            .with_log_group_fields("/a", vec![("@message", 100), ("level", 40)])
+            .with_log_group_fields("/b", vec![("level", 90), ("user", 10)]);
+
+        serve_all(&app, &backend, vec![AwsReq::GetLogGroupFields]);
+        app.lock().unwrap().log_groups.selected_log_groups.push("/b".to_string());
+        serve_all(&app, &backend, vec![AwsReq::GetLogGroupFields]);
+
+        assert_eq!(*backend.fields_requests.lock().unwrap(), vec!["/a", "/b"]);
+        let app = app.lock().unwrap();
+        let fields: Vec<_> =
+            app.fields.fields.iter().map(|f| (f.name.as_str(), f.percent)).collect();
+        assert_eq!(fields, vec![("@message", 100), ("level", 90), ("user", 10)]);
+    }
+
+    #[test]
+    fn run_query_rejects_invalid_query_before_starting() {
+        let app = Arc::new(Mutex::new(App {
+            query: "fields @timestamp, @message\n| limit ten".to_string(),
+            ..App::default()
+        }));
+        let backend = FakeBackend::default();
+
+        serve_all(&app, &backend, vec![AwsReq::RunQuery(0)]);
+
+        assert!(backend.started_queries.lock().unwrap().is_empty());
+        let app = app.lock().unwrap();
+        assert!(app.status_message.text().starts_with("Invalid query at line 2, column 9:"));
+        assert!(app.log_results.progress.is_none());
+    }
+}
@@ -0,0 +1,186 @@
+use std::{
+    future::Future,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use log::warn;
+
+use super::{
+    backend::BackendResult,
+    error::{BackendError, ErrorKind},
+};
+
+/// How often, and after how long, a request failing with a transient error is retried.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct RetryPolicy {
+    pub(crate) max_retries: u32,
+    pub(crate) base_delay: Duration,
+    pub(crate) max_delay: Duration,
+}
+
+impl RetryPolicy {
+    /// The delay before retry `attempt`, counting from 1. Doubles with every attempt up to
+    /// `max_delay`, with the upper half of it picked at random so clients throttled together
+    /// don't retry together.
+    pub(crate) fn delay(&self, attempt: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempt.saturating_sub(1));
+        let delay = self.base_delay.saturating_mul(factor).min(self.max_delay);
+        delay / 2 + jitter(delay / 2)
+    }
+}
+
+/// A pseudo random duration up to `max`, good enough to spread out retries.
+fn jitter(max: Duration) -> Duration {
+    if max.is_zero() {
+        return max;
+    }
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().subsec_nanos();
+    Duration::from_nanos(nanos as u64 % max.as_nanos().min(u64::MAX as u128) as u64)
+}
+
+/// Throttling, quota and network errors are expected to go away by themselves.
+fn is_transient(err: &BackendError) -> bool {
+    matches!(err.kind, ErrorKind::Throttling | ErrorKind::LimitExceeded | ErrorKind::Network)
+}
+
+/// Runs `request` until it succeeds, fails with an error that isn't transient or runs out of
+/// retries. `on_retry` is called with the attempt, the error and the delay before every retry.
+pub(crate) async fn with_retry<T, F, Fut>(
+    policy: &RetryPolicy,
+    mut on_retry: impl FnMut(u32, &BackendError, Duration),
+    mut request: F,
+) -> BackendResult<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = BackendResult<T>>,
+{
+    let mut attempt = 0;
+    loop {
+        match request().await {
+            Err(err) if attempt < policy.max_retries && is_transient(&err) => {
+                attempt += 1;
+                let delay = policy.delay(attempt);
+                warn!("retry {} in {:?} after {:?}", attempt, delay, err);
+                on_retry(attempt, &err, delay);
+                tokio::time::sleep(delay).await;
+            }
+            res => return res,
+        }
+    }
+}
+
+/// Describes a retry of `what` for the status bar.
+pub(crate) fn retry_message(
+    what: &str,
+    policy: &RetryPolicy,
+    attempt: u32,
+    err: &BackendError,
+    delay: Duration,
+) -> String {
+    format!(
+        "{} failed with {}, retry {}/{} in {:.1}s",
+        what,
+        err.code.as_deref().unwrap_or_else(|| err.summary()),
+        attempt,
+        policy.max_retries,
+        delay.as_secs_f64()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use super::*;
+
+    fn policy(max_retries: u32, base_ms: u64, max_ms: u64) -> RetryPolicy {
+        RetryPolicy {
+            max_retries,
+            base_delay: Duration::from_millis(base_ms),
+            max_delay: Duration::from_millis(max_ms),
+        }
+    }
+
+    fn run<T>(future: impl Future<Output = T>) -> T {
+        tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap().block_on(future)
+    }
+
+    #[test]
+    fn delay_doubles_up_to_the_cap_with_jitter_in_the_upper_half() {
+        let policy = policy(10, 100, 1000);
+        let cases = [(1, 100), (2, 200), (3, 400), (4, 800), (5, 1000), (6, 1000), (64, 1000)];
+        for (attempt, full) in cases {
+            for _ in 0..20 {
+                let delay = policy.delay(attempt).as_millis() as u64;
+                assert!(delay >= full / 2 && delay < full, "{}: {}ms", attempt, delay);
+            }
+        }
+    }
+
+    #[test]
+    fn jitter_stays_below_its_maximum() {
+        assert_eq!(jitter(Duration::ZERO), Duration::ZERO);
+        for _ in 0..100 {
+            assert!(jitter(Duration::from_nanos(7)) < Duration::from_nanos(7));
+        }
+    }
+
+    #[test]
+    fn transient_errors_are_retried_until_the_retries_run_out() {
+        let calls = Cell::new(0);
+        let mut attempts = vec![];
+
+        let res: BackendResult<()> = run(with_retry(
+            &policy(3, 1, 2),
+            |attempt, _, _| attempts.push(attempt),
+            || {
+                calls.set(calls.get() + 1);
+                async { Err(BackendError::new(ErrorKind::Throttling, "slow down")) }
+            },
+        ));
+
+        assert_eq!(res.unwrap_err().kind, ErrorKind::Throttling);
+        assert_eq!(calls.get(), 4);
+        assert_eq!(attempts, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn requests_succeeding_after_a_retry_return_their_result() {
+        let calls = Cell::new(0);
+
+        let res = run(with_retry(
+            &policy(3, 1, 2),
+            |_, _, _| {},
+            || {
+                calls.set(calls.get() + 1);
+                let failed = calls.get() < 3;
+                async move {
+                    match failed {
+                        true => Err(BackendError::new(ErrorKind::Network, "timed out")),
+                        false => Ok("done"),
+                    }
+                }
+            },
+        ));
+
+        assert_eq!(res.unwrap(), "done");
+        assert_eq!(calls.get(), 3);
+    }
+
+    #[test]
+    fn other_errors_are_not_retried() {
+        let calls = Cell::new(0);
+
+        let res: BackendResult<()> = run(with_retry(
+            &policy(3, 1, 2),
+            |_, _, _| panic!("retried"),
+            || {
+                calls.set(calls.get() + 1);
+                async { Err(BackendError::new(ErrorKind::AccessDenied, "no")) }
+            },
+        ));
+
+        assert_eq!(res.unwrap_err().kind, ErrorKind::AccessDenied);
+        assert_eq!(calls.get(), 1);
+    }
+}
@@ -0,0 +1,102 @@
+use std::{collections::HashMap, io::Stdout};
+
+use crate::{cwl::backend::LogGroupField, overview::scroll_window, Mode, Widget};
+use crossterm::event::KeyCode;
+use tui::{
+    backend::CrosstermBackend,
+    layout::Rect,
+    style::{Color, Style},
+    text::{Span, Spans},
+    widgets::{Block, Borders, List, ListItem},
+    Frame,
+};
+
+/// Fields discovered in the selected log groups, offered as completions and listed next to
+/// the query.
+#[derive(Default)]
+pub(crate) struct Fields {
+    /// Fields per log group, kept until the client is rebuilt for another account or region.
+    pub(crate) by_log_group: HashMap<String, Vec<LogGroupField>>,
+    /// Fields of the selected log groups, the most common first.
+    pub(crate) fields: Vec<LogGroupField>,
+    selected: usize,
+}
+
+impl Fields {
+    /// Merges the fields of `log_groups`, a field found in several of them gets the highest
+    /// percentage.
+    pub(crate) fn update(&mut self, log_groups: &[String]) {
+        let mut merged: HashMap<&str, i32> = HashMap::new();
+        for field in log_groups.iter().filter_map(|g| self.by_log_group.get(g)).flatten() {
+            let percent = merged.entry(&field.name).or_insert(0);
+            *percent = (*percent).max(field.percent);
+        }
+        let mut fields: Vec<LogGroupField> = merged
+            .into_iter()
+            .map(|(name, percent)| LogGroupField { name: name.to_string(), percent })
+            .collect();
+        fields.sort_by(|a, b| b.percent.cmp(&a.percent).then_with(|| a.name.cmp(&b.name)));
+        self.fields = fields;
+        self.selected = self.selected.min(self.fields.len().saturating_sub(1));
+    }
+
+    pub(crate) fn names(&self) -> impl Iterator<Item = &str> {
+        self.fields.iter().map(|f| f.name.as_str())
+    }
+}
+
+pub(crate) fn draw(
+    app: &std::sync::MutexGuard<crate::App>,
+    frame: &mut Frame<CrosstermBackend<Stdout>>,
+    area: Rect,
+    style: Style,
+) {
+    let fields = &app.fields;
+    let highlight = app.focused == Widget::Fields && app.mode == Mode::Insert;
+    let height = area.height.saturating_sub(2) as usize;
+    let (win_start, win_end) = scroll_window(fields.selected, fields.fields.len(), height);
+    let items: Vec<ListItem> = fields.fields[win_start..win_end]
+        .iter()
+        .enumerate()
+        .map(|(i, field)| {
+            ListItem::new(Spans::from(vec![
+                Span::raw(format!("{:>4}% ", field.percent)),
+                Span::raw(field.name.as_str()),
+            ]))
+            .style(if highlight && i + win_start == fields.selected {
+                Style::default().fg(Color::Red)
+            } else {
+                Style::default()
+            })
+        })
+        .collect();
+    let list = List::new(items).block(Block::default().style(style).borders(Borders::ALL).title(
+        if app.log_groups.selected_log_groups.is_empty() {
+            "fields (select log groups)"
+        } else {
+            "fields"
+        },
+    ));
+    frame.render_widget(list, area);
+}
+
+pub(crate) fn handle_input(mut app: std::sync::MutexGuard<crate::App>, key_code: KeyCode) {
+    let len = app.fields.fields.len();
+    let selected = app.fields.selected;
+    match key_code {
+        KeyCode::Esc => app.mode = Mode::Normal,
+        KeyCode::Char('j') | KeyCode::Down => {
+            app.fields.selected = if len > 0 { (selected + 1) % len } else { 0 };
+        }
+        KeyCode::Char('k') | KeyCode::Up => {
+            app.fields.selected = if selected > 0 && len > 0 {
+                (selected - 1) % len
+            } else if selected == 0 && len > 0 {
+                len - 1
+            } else {
+                0
+            };
+        }
+        _ => {}
+    }
+}
@@ -0,0 +1,278 @@
+use std::{collections::HashMap, io::Stdout, sync::mpsc::Sender};
+
+use crate::{
+    chart::{format_time, parse_timestamp},
+    cwl::AwsReq,
+    overview::{display_offset, ResultRow},
+    query_syntax::{command_is, commands},
+    status_bar::StatusMessage,
+    time_select::TimeSelector,
+    Mode, Widget,
+};
+use crossterm::event::KeyCode;
+use tui::{
+    backend::CrosstermBackend,
+    layout::Rect,
+    style::{Color, Style},
+    text::{Span, Spans},
+    widgets::{Block, Borders, Paragraph},
+    Frame,
+};
+
+/// The bin size is picked so the time range is split into at most this many bins.
+const MAX_BINS: i64 = 60;
+/// Bin sizes in seconds and how they are written in `bin()`, smallest first.
+const BIN_SIZES: [(i64, &str); 13] = [
+    (1, "1s"),
+    (5, "5s"),
+    (10, "10s"),
+    (30, "30s"),
+    (60, "1m"),
+    (300, "5m"),
+    (600, "10m"),
+    (1800, "30m"),
+    (3600, "1h"),
+    (3 * 3600, "3h"),
+    (6 * 3600, "6h"),
+    (12 * 3600, "12h"),
+    (24 * 3600, "1d"),
+];
+const BLOCKS: [char; 9] = [' ', '▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// Number of matching events per bin over the time range of the latest query.
+#[derive(Debug, PartialEq)]
+pub(crate) struct Histogram {
+    /// Bin size in seconds.
+    pub(crate) bin: i64,
+    /// Start of every bin in the time range as a unix timestamp with its count, oldest first.
+    pub(crate) buckets: Vec<(i64, u64)>,
+    selected: usize,
+}
+
+impl Histogram {
+    /// Counts of a `stats count(*) by bin()` query, bins without events are left out of the
+    /// results so they are filled in with 0. Rows for the same bin, like those of different
+    /// targets, are added up.
+    pub(crate) fn from_results(rows: &[ResultRow], start: i64, end: i64, bin: i64) -> Self {
+        let bin_column = format!("bin({})", bin_name(bin));
+        let mut counts: HashMap<i64, u64> = HashMap::new();
+        for row in rows {
+            let time = row.get(&bin_column).and_then(parse_timestamp);
+            let count = row.get("count(*)").and_then(|c| c.parse::<u64>().ok());
+            if let (Some(time), Some(count)) = (time, count) {
+                *counts.entry(time.unix_timestamp()).or_insert(0) += count;
+            }
+        }
+        let first = start - start.rem_euclid(bin);
+        let buckets = (0..)
+            .map(|i| first + i * bin)
+            .take_while(|t| *t < end.max(first + 1))
+            .map(|t| (t, counts.get(&t).copied().unwrap_or(0)))
+            .collect();
+        Self { bin, buckets, selected: 0 }
+    }
+}
+
+/// The smallest bin size that splits the range into at most [`MAX_BINS`] bins.
+pub(crate) fn bin_size(start: i64, end: i64) -> i64 {
+    BIN_SIZES
+        .iter()
+        .map(|(size, _)| *size)
+        .find(|size| (end - start + size - 1) / size <= MAX_BINS)
+        .unwrap_or(BIN_SIZES[BIN_SIZES.len() - 1].0)
+}
+
+fn bin_name(bin: i64) -> &'static str {
+    BIN_SIZES.iter().find(|(size, _)| *size == bin).map(|(_, name)| *name).unwrap_or("1d")
+}
+
+/// A query counting the events `query` matches per bin. Commands that only change which fields
+/// are shown or how many rows there are, and everything from the first `stats` on, are left
+/// out so all matching events are counted.
+pub(crate) fn histogram_query(query: &str, bin: i64) -> String {
+    let mut kept: Vec<String> = commands(query)
+        .into_iter()
+        .take_while(|c| !command_is(c, "stats"))
+        .filter(|c| !["sort", "limit", "display"].iter().any(|name| command_is(c, name)))
+        .collect();
+    kept.push(format!("stats count(*) by bin({})", bin_name(bin)));
+    kept.join(" | ")
+}
+
+pub(crate) fn draw(
+    app: &std::sync::MutexGuard<crate::App>,
+    frame: &mut Frame<CrosstermBackend<Stdout>>,
+    area: Rect,
+    style: Style,
+) {
+    let block = Block::default().style(style).borders(Borders::ALL);
+    let histogram = match &app.log_results.histogram {
+        Some(histogram) if !histogram.buckets.is_empty() => histogram,
+        _ => {
+            frame.render_widget(block.title("histogram (run the query to fill it)"), area);
+            return;
+        }
+    };
+    let highlight = app.focused == Widget::Histogram && app.mode == Mode::Insert;
+    let offset = display_offset(app);
+    let buckets = &histogram.buckets;
+    let max = buckets.iter().map(|(_, count)| *count).max().unwrap_or(0).max(1);
+    let title = if highlight {
+        let (start, count) = buckets[histogram.selected];
+        let span = histogram.bin as f64;
+        format!(
+            "histogram: {} - {}, {} events (Enter to narrow the time range)",
+            format_time(start as f64, span, offset),
+            format_time((start + histogram.bin) as f64, span, offset),
+            count
+        )
+    } else {
+        format!("histogram (bin {}, max {})", bin_name(histogram.bin), max)
+    };
+
+    let height = area.height.saturating_sub(2) as usize;
+    let width = area.width.saturating_sub(2) as usize;
+    let bar_width = (width / buckets.len()).clamp(1, 4);
+    let visible = (width / bar_width).max(1);
+    let first = (histogram.selected + 1).saturating_sub(visible);
+    let levels = (height * 8) as u64;
+    let lines: Vec<Spans> = (0..height)
+        .map(|row| {
+            let floor = ((height - 1 - row) * 8) as u64;
+            let spans = buckets
+                .iter()
+                .enumerate()
+                .skip(first)
+                .take(visible)
+                .map(|(i, (_, count))| {
+                    let level = (*count as f64 / max as f64 * levels as f64).ceil() as u64;
+                    let fill = level.saturating_sub(floor).min(8) as usize;
+                    let bar = BLOCKS[fill].to_string().repeat(bar_width);
+                    if highlight && i == histogram.selected {
+                        Span::styled(bar, Style::default().fg(Color::Red))
+                    } else {
+                        Span::styled(bar, Style::default().fg(Color::Cyan))
+                    }
+                })
+                .collect::<Vec<_>>();
+            Spans::from(spans)
+        })
+        .collect();
+    frame.render_widget(Paragraph::new(lines).block(block.title(title)), area);
+}
+
+pub(crate) fn handle_input(
+    mut app: std::sync::MutexGuard<crate::App>,
+    key_code: KeyCode,
+    cwl: &Sender<AwsReq>,
+) {
+    let histogram = match app.log_results.histogram.as_mut() {
+        Some(histogram) if !histogram.buckets.is_empty() => histogram,
+        _ => {
+            app.mode = Mode::Normal;
+            return;
+        }
+    };
+    let len = histogram.buckets.len();
+    match key_code {
+        KeyCode::Esc => app.mode = Mode::Normal,
+        KeyCode::Char('l') | KeyCode::Right => histogram.selected = (histogram.selected + 1) % len,
+        KeyCode::Char('h') | KeyCode::Left => {
+            histogram.selected = (histogram.selected + len - 1) % len
+        }
+        KeyCode::Enter => {
+            let (start, _) = histogram.buckets[histogram.selected];
+            // The end of a time range is inclusive, the next bin starts at `start + bin`.
+            let end = start + histogram.bin - 1;
+            match TimeSelector::from_timestamps(start, end) {
+                Ok(time_selector) => {
+                    app.time_selector = time_selector;
+                    app.mode = Mode::Normal;
+                    app.status_message = StatusMessage::info(&format!(
+                        "Narrowed the time range to {}",
+                        app.time_selector
+                    ));
+                    cwl.send(AwsReq::RunQuery(app.tabs.active_id())).unwrap();
+                }
+                Err(err) => app.status_message = StatusMessage::error(err),
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(bin: &str, count: &str) -> ResultRow {
+        ResultRow {
+            fields: vec![
+                ("bin(1m)".to_string(), bin.to_string()),
+                ("count(*)".to_string(), count.to_string()),
+            ],
+        }
+    }
+
+    #[test]
+    fn query_keeps_only_what_selects_events() {
+        let query = "fields @timestamp, @message\n\
+            | filter @message like /error/ # sort | limit in a comment\n\
+            | SORT @timestamp desc\n\
+            | display @message\n\
+            | limit 20\n\
+            | stats count(*) by @logStream\n\
+            | filter count > 1";
+
+        assert_eq!(
+            histogram_query(query, 300),
+            "fields @timestamp, @message | filter @message like /error/ | stats count(*) by bin(5m)"
+        );
+    }
+
+    #[test]
+    fn query_without_anything_to_leave_out_is_kept() {
+        let query = "fields @message\n| filter @message like /sorted/\n| parse @message 'a=*' as a";
+
+        assert_eq!(
+            histogram_query(query, 3600),
+            "fields @message | filter @message like /sorted/ | parse @message 'a=*' as a \
+                | stats count(*) by bin(1h)"
+        );
+    }
+
+    #[test]
+    fn bin_size_keeps_the_number_of_bins_down() {
+        let cases = [
+            (0, 1),
+            (60, 1),
+            (61, 5),
+            (3600, 60),
+            (3601, 300),
+            (24 * 3600, 1800),
+            (30 * 24 * 3600, 12 * 3600),
+            (365 * 24 * 3600, 24 * 3600),
+        ];
+        for (range, bin) in cases {
+            assert_eq!(bin_size(1_000, 1_000 + range), bin, "{}", range);
+        }
+    }
+
+    #[test]
+    fn missing_bins_are_filled_and_targets_added_up() {
+        let start = 1_635_760_800;
+        let rows = vec![
+            row("2021-11-01 10:02:00.000", "4"),
+            row("2021-11-01 10:00:00.000", "7"),
+            row("2021-11-01 10:02:00.000", "1"),
+            row("not a time", "9"),
+        ];
+
+        let histogram = Histogram::from_results(&rows, start + 30, start + 240, 60);
+
+        assert_eq!(
+            histogram.buckets,
+            vec![(start, 7), (start + 60, 0), (start + 120, 5), (start + 180, 0)]
+        );
+    }
+}
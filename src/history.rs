@@ -0,0 +1,267 @@
+use std::{
+    fs::{self, OpenOptions},
+    io::{self, Stdout, Write},
+    path::{Path, PathBuf},
+    sync::mpsc::Sender,
+};
+
+use crate::{
+    cwl::{
+        backend::{QueryStatistics, QueryStatus},
+        AwsReq,
+    },
+    status_bar::{self, StatusMessage},
+    time_select::TimeSelector,
+    SelectedView, Widget,
+};
+use crossterm::event::KeyCode;
+use log::warn;
+use serde::{Deserialize, Serialize};
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+use tui::{
+    backend::CrosstermBackend,
+    layout::{Constraint, Direction, Layout},
+    style::{Color, Style},
+    text::{Span, Spans},
+    widgets::{Block, Borders, List, ListItem, Paragraph},
+    Frame,
+};
+
+const HISTORY_FILE: &str = "history.jsonl";
+/// Only the most recent runs are kept in memory, the file itself is never truncated.
+const MAX_ENTRIES: usize = 1000;
+
+/// A single run of a query, appended to the history file once the query stops.
+#[derive(Clone, Serialize, Deserialize)]
+pub(crate) struct HistoryEntry {
+    pub(crate) query: String,
+    pub(crate) log_groups: Vec<String>,
+    pub(crate) start: i64,
+    pub(crate) end: i64,
+    pub(crate) status: QueryStatus,
+    pub(crate) records: usize,
+    pub(crate) statistics: QueryStatistics,
+}
+
+#[derive(Default)]
+pub(crate) struct History {
+    pub(crate) entries: Vec<HistoryEntry>,
+    path: PathBuf,
+    selected: usize,
+}
+
+impl History {
+    /// A history without entries that records runs to the history file in `dir`.
+    pub(crate) fn empty(dir: &Path) -> Self {
+        Self { entries: vec![], path: dir.join(HISTORY_FILE), selected: 0 }
+    }
+
+    /// Reads the history from `dir`, a missing file means nothing has been run yet.
+    ///
+    /// Lines that can't be read, like one cut off by a crash while it was written, are skipped
+    /// so the rest of the history is kept.
+    pub(crate) fn load(dir: &Path) -> io::Result<Self> {
+        let path = dir.join(HISTORY_FILE);
+        let mut entries = vec![];
+        match fs::read_to_string(&path) {
+            Ok(content) => {
+                for (i, line) in content.lines().enumerate().filter(|(_, l)| !l.trim().is_empty()) {
+                    match serde_json::from_str(line) {
+                        Ok(entry) => entries.push(entry),
+                        Err(err) => {
+                            warn!("Skipping line {} of {}: {}", i + 1, path.display(), err)
+                        }
+                    }
+                }
+            }
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+            Err(err) => return Err(err),
+        };
+        let skip = entries.len().saturating_sub(MAX_ENTRIES);
+        entries.drain(..skip);
+        Ok(Self { entries, path, selected: 0 })
+    }
+
+    /// Adds a run to the history and appends it to the history file.
+    pub(crate) fn record(&mut self, entry: HistoryEntry) -> io::Result<()> {
+        let line = serde_json::to_string(&entry)?;
+        self.entries.push(entry);
+        if self.entries.len() > MAX_ENTRIES {
+            self.entries.remove(0);
+        }
+        let mut file = OpenOptions::new().create(true).append(true).open(&self.path)?;
+        writeln!(file, "{}", line)
+    }
+
+    /// The entry shown at `index`, newest first.
+    fn get(&self, index: usize) -> Option<&HistoryEntry> {
+        self.entries.iter().rev().nth(index)
+    }
+}
+
+fn format_timestamp(timestamp: i64) -> String {
+    OffsetDateTime::from_unix_timestamp(timestamp)
+        .ok()
+        .and_then(|dt| dt.format(&Rfc3339).ok())
+        .unwrap_or_else(|| timestamp.to_string())
+}
+
+pub(crate) fn draw(
+    app: std::sync::MutexGuard<crate::App>,
+    frame: &mut Frame<CrosstermBackend<Stdout>>,
+) {
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(2)
+        .constraints(
+            [
+                Constraint::Min(1),
+                Constraint::Length(9),
+                Constraint::Length(3),
+                Constraint::Length(2),
+            ]
+            .as_ref(),
+        )
+        .split(frame.size());
+
+    let history = &app.history;
+    let height = chunks[0].height.saturating_sub(2) as usize;
+    let (win_start, win_end) =
+        crate::overview::scroll_window(history.selected, history.entries.len(), height);
+    let items: Vec<ListItem> = history
+        .entries
+        .iter()
+        .rev()
+        .enumerate()
+        .skip(win_start)
+        .take(win_end - win_start)
+        .map(|(i, e)| {
+            let content = vec![Spans::from(Span::raw(format!(
+                "{} - {} {:?} {} records, {} scanned, {} bytes [{}]",
+                format_timestamp(e.start),
+                format_timestamp(e.end),
+                e.status,
+                e.records,
+                e.statistics.records_scanned,
+                e.statistics.bytes_scanned,
+                e.log_groups.join(", "),
+            )))];
+            ListItem::new(content).style(if i == history.selected {
+                Style::default().fg(Color::Red)
+            } else {
+                Style::default()
+            })
+        })
+        .collect();
+    let list = List::new(items).block(
+        Block::default()
+            .style(Style::default().fg(Color::Yellow))
+            .borders(Borders::ALL)
+            .title("query history"),
+    );
+    frame.render_widget(list, chunks[0]);
+
+    let query = history.get(history.selected).map(|e| e.query.as_str()).unwrap_or("");
+    let query = Paragraph::new(query).block(Block::default().borders(Borders::ALL).title("query"));
+    frame.render_widget(query, chunks[1]);
+
+    status_bar::draw(app, frame, chunks[2]);
+
+    let controls = Paragraph::new(
+        "Enter (re-run with the same time range) | n (re-run with the range ending now) | Escape (go back)",
+    )
+    .block(Block::default().borders(Borders::NONE).title("controls"));
+    frame.render_widget(controls, chunks[3]);
+}
+
+pub(crate) fn handle_input(
+    mut app: std::sync::MutexGuard<crate::App>,
+    key_code: KeyCode,
+    cwl: &Sender<AwsReq>,
+) {
+    match key_code {
+        KeyCode::Esc => {
+            app.selected = SelectedView::Overview;
+            app.focused = Widget::Query;
+        }
+        KeyCode::Char('j') | KeyCode::Down => {
+            let len = app.history.entries.len();
+            app.history.selected = if len > 0 { (app.history.selected + 1) % len } else { 0 };
+        }
+        KeyCode::Char('k') | KeyCode::Up => {
+            let l = app.history.entries.len();
+            let r = app.history.selected;
+            app.history.selected = if r > 0 && l > 0 {
+                (r - 1) % l
+            } else if r == 0 && l > 0 {
+                l - 1
+            } else {
+                0
+            };
+        }
+        KeyCode::Enter => {
+            if let Some(entry) = app.history.get(app.history.selected).cloned() {
+                match TimeSelector::from_timestamps(entry.start, entry.end) {
+                    Ok(time_selector) => rerun(app, entry, time_selector, cwl),
+                    Err(err) => app.status_message = StatusMessage::error(err),
+                }
+            }
+        }
+        KeyCode::Char('n') => {
+            if let Some(entry) = app.history.get(app.history.selected).cloned() {
+                let time_selector = TimeSelector::last(entry.end - entry.start);
+                rerun(app, entry, time_selector, cwl);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn rerun(
+    mut app: std::sync::MutexGuard<crate::App>,
+    entry: HistoryEntry,
+    time_selector: TimeSelector,
+    cwl: &Sender<AwsReq>,
+) {
+    app.query = entry.query;
+    app.log_groups.selected_log_groups = entry.log_groups;
+    app.time_selector = time_selector;
+    app.selected = SelectedView::Overview;
+    app.focused = Widget::LogRows;
+    cwl.send(AwsReq::RunQuery(app.tabs.active_id())).unwrap();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(query: &str) -> HistoryEntry {
+        HistoryEntry {
+            query: query.to_string(),
+            log_groups: vec!["/a".to_string()],
+            start: 0,
+            end: 60,
+            status: QueryStatus::Complete,
+            records: 1,
+            statistics: QueryStatistics::default(),
+        }
+    }
+
+    #[test]
+    fn bad_lines_are_skipped_and_new_runs_still_recorded() {
+        let dir = std::env::temp_dir().join(format!("rcwi-history-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let good = serde_json::to_string(&entry("first")).unwrap();
+        fs::write(dir.join(HISTORY_FILE), format!("{}\n{{\"query\": \"trunc\n", good)).unwrap();
+
+        let mut history = History::load(&dir).unwrap();
+        assert_eq!(history.entries.len(), 1);
+        assert_eq!(history.entries[0].query, "first");
+
+        history.record(entry("second")).unwrap();
+        let reloaded = History::load(&dir).unwrap();
+        let queries: Vec<&str> = reloaded.entries.iter().map(|e| e.query.as_str()).collect();
+        assert_eq!(queries, vec!["first", "second"]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
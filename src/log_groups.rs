@@ -178,6 +178,7 @@ pub(crate) fn handle_input(
                     if num_selected_before == app.log_groups.selected_log_groups.len() {
                         app.log_groups.selected_log_groups.push(value);
                     }
+                    cwl.send(AwsReq::GetLogGroupFields).unwrap();
                 }
                 _ => {}
             },
@@ -0,0 +1,101 @@
+use std::{io::Stdout, sync::mpsc::Sender};
+
+use crate::{clipboard, cwl::AwsReq, overview::scroll_window, status_bar, SelectedView, Widget};
+use crossterm::event::KeyCode;
+use tui::{
+    backend::CrosstermBackend,
+    layout::{Constraint, Direction, Layout},
+    style::{Color, Style},
+    widgets::{Block, Borders, Paragraph, Row, Table, Wrap},
+    Frame,
+};
+use unicode_width::UnicodeWidthStr;
+
+/// The full log record behind a result row, as returned by `GetLogRecord`.
+#[derive(Default)]
+pub(crate) struct LogRecord {
+    pub(crate) fields: Vec<(String, String)>,
+    selected: usize,
+}
+
+pub(crate) fn draw(
+    app: std::sync::MutexGuard<crate::App>,
+    frame: &mut Frame<CrosstermBackend<Stdout>>,
+) {
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(2)
+        .constraints([Constraint::Min(1), Constraint::Length(10), Constraint::Length(3)].as_ref())
+        .split(frame.size());
+
+    let record = &app.log_record;
+    let height = chunks[0].height.saturating_sub(2) as usize;
+    let (win_start, win_end) = scroll_window(record.selected, record.fields.len(), height);
+    let field_width = record.fields.iter().map(|(f, _)| f.width()).max().unwrap_or(0);
+    let rows: Vec<Row> = record.fields[win_start..win_end]
+        .iter()
+        .enumerate()
+        .map(|(i, (field, value))| {
+            Row::new(vec![field.clone(), value.replace('\n', " ")]).style(
+                if i + win_start == record.selected {
+                    Style::default().fg(Color::Red)
+                } else {
+                    Style::default()
+                },
+            )
+        })
+        .collect();
+    let widths = [Constraint::Length(field_width as u16), Constraint::Min(1)];
+    let fields = Table::new(rows).widths(&widths).column_spacing(2).block(
+        Block::default()
+            .style(Style::default().fg(Color::Yellow))
+            .borders(Borders::ALL)
+            .title("log record"),
+    );
+    frame.render_widget(fields, chunks[0]);
+
+    let (title, value) = match record.fields.get(record.selected) {
+        Some((field, value)) => (field.as_str(), value.as_str()),
+        None => ("", ""),
+    };
+    let value = Paragraph::new(value)
+        .wrap(Wrap { trim: false })
+        .block(Block::default().borders(Borders::ALL).title(title));
+    frame.render_widget(value, chunks[1]);
+
+    status_bar::draw(app, frame, chunks[2]);
+}
+
+pub(crate) fn handle_input(
+    mut app: std::sync::MutexGuard<crate::App>,
+    key_code: KeyCode,
+    _cwl: &Sender<AwsReq>,
+) {
+    match key_code {
+        KeyCode::Esc => {
+            app.selected = SelectedView::Overview;
+            app.focused = Widget::LogRows;
+        }
+        KeyCode::Char('j') | KeyCode::Down => {
+            let len = app.log_record.fields.len();
+            app.log_record.selected = if len > 0 { (app.log_record.selected + 1) % len } else { 0 };
+        }
+        KeyCode::Char('k') | KeyCode::Up => {
+            let l = app.log_record.fields.len();
+            let r = app.log_record.selected;
+            app.log_record.selected = if r > 0 && l > 0 {
+                (r - 1) % l
+            } else if r == 0 && l > 0 {
+                l - 1
+            } else {
+                0
+            };
+        }
+        KeyCode::Char('y') => {
+            if let Some((_, value)) = app.log_record.fields.get(app.log_record.selected) {
+                app.status_message = clipboard::yank(value.clone());
+            }
+        }
+        _ => {}
+    }
+}
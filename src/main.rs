@@ -1,22 +1,35 @@
+use config::Config;
 use crossterm::{
     event::{poll, read, DisableMouseCapture, EnableMouseCapture, Event as CEvent, KeyCode},
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
 use editor_input::input_from_editor;
+use fields::Fields;
 use flexi_logger::{FileSpec, Logger};
+use history::History;
+use log::error;
 use log_groups::LogGroups;
+use log_record::LogRecord;
 use overview::LogResults;
+use profiles::Profiles;
+use query_editor::QueryEditor;
+use saved_queries::SavedQueries;
 use std::sync::{
     mpsc::{Receiver, Sender},
     Arc, Mutex,
 };
+use tabs::Tabs;
+use tail::Tail;
 
-use std::{error::Error, io::stdout, time::Duration};
+use std::{error::Error, io::stdout, path::PathBuf, time::Duration};
 use tui::{backend::CrosstermBackend, Terminal};
 
 use crate::{cwl::AwsReq, status_bar::StatusMessage, time_select::TimeSelector};
 
+const DEFAULT_QUERY: &str = "fields @timestamp, @message\n\
+    | sort @timestamp desc\n";
+
 #[derive(Clone, Copy, PartialEq, Eq)]
 enum Widget {
     LogGroups,
@@ -24,12 +37,19 @@ enum Widget {
     Query,
     LogRows,
     TimeSelector,
+    Fields,
+    Histogram,
 }
 
 #[derive(Clone, Copy, PartialEq, Eq)]
 enum SelectedView {
     Overview,
     LogGroups,
+    LogRecord,
+    SavedQueries,
+    History,
+    Profiles,
+    Tail,
 }
 
 #[derive(Clone, Copy, PartialEq, Eq)]
@@ -47,8 +67,25 @@ struct App {
     quit: bool,
     query: String,
     log_results: LogResults,
+    log_record: LogRecord,
+    saved_queries: SavedQueries,
+    history: History,
     status_message: StatusMessage,
     time_selector: TimeSelector,
+    config: Config,
+    profiles: Profiles,
+    /// Region of the current client, `None` until it has been built.
+    aws_region: Option<String>,
+    tail: Tail,
+    query_editor: QueryEditor,
+    fields: Fields,
+    /// Shows the detail of the status message in a popup.
+    status_detail_open: bool,
+    /// Tabs other than the active one, whose query, log groups, time range and results are
+    /// in the fields above.
+    tabs: Tabs,
+    /// Shows the histogram of matching events above the results.
+    show_histogram: bool,
 }
 
 impl Default for App {
@@ -58,30 +95,110 @@ impl Default for App {
             focused: Widget::LogGroups,
             mode: Mode::Normal,
             break_inner: false,
-            query: "fields @timestamp, @message\n\
-        | sort @timestamp desc\n"
-                .to_string(),
+            query: DEFAULT_QUERY.to_string(),
             quit: false,
             log_results: LogResults::default(),
+            log_record: LogRecord::default(),
+            saved_queries: SavedQueries::default(),
+            history: History::default(),
             status_message: StatusMessage::default(),
             log_groups: LogGroups::default(),
             time_selector: TimeSelector::default(),
+            config: Config::default(),
+            profiles: Profiles::default(),
+            aws_region: None,
+            tail: Tail::default(),
+            query_editor: QueryEditor::default(),
+            fields: Fields::default(),
+            status_detail_open: false,
+            tabs: Tabs::default(),
+            show_histogram: false,
         }
     }
 }
+mod chart;
+mod cli;
+mod clipboard;
+mod config;
 mod controls_bar;
 mod cwl;
+mod fields;
+mod histogram;
+mod history;
 mod log_groups;
+mod log_record;
+mod output;
 mod overview;
+mod profiles;
+mod query_editor;
+mod query_lint;
+mod query_syntax;
+mod results_search;
+mod saved_queries;
 mod status_bar;
+mod tabs;
+mod tail;
 mod time_select;
 
+/// Directory for everything rcwi stores, like logs, config, saved queries and history.
+fn rcwi_dir() -> PathBuf {
+    home::home_dir().expect("user missing home dir").join(".rcwi")
+}
+
 fn main() -> Result<(), Box<dyn Error>> {
-    let app = Arc::new(Mutex::new(App::default()));
-    let log_dir = home::home_dir().expect("user missing home dir").join(".rcwi");
-    Logger::try_with_str("info")?
-        .log_to_file(FileSpec::default().directory(log_dir).suppress_timestamp())
+    let config = match Config::load(&rcwi_dir()) {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+    };
+    Logger::try_with_str(config.log_level())?
+        .log_to_file(FileSpec::default().directory(rcwi_dir()).suppress_timestamp())
         .start()?;
+
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if let Some(command) = args.first() {
+        let res = match command.as_str() {
+            "query" => cli::run_query(&args[1..], &config),
+            "-h" | "--help" => {
+                println!("{}", cli::USAGE);
+                Ok(())
+            }
+            _ => Err(format!("Unknown command '{}'\n\n{}", command, cli::USAGE).into()),
+        };
+        if let Err(err) = res {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
+    let mut app = App {
+        query: config.query().to_string(),
+        time_selector: config.time_selector()?,
+        show_histogram: config.histogram,
+        config,
+        ..App::default()
+    };
+    app.log_groups.selected_log_groups = app.config.log_groups.clone();
+    match SavedQueries::load(&rcwi_dir()) {
+        Ok(saved_queries) => app.saved_queries = saved_queries,
+        Err(err) => {
+            error!("Loading saved queries failed: {:?}", err);
+            app.saved_queries = SavedQueries::empty(&rcwi_dir());
+            app.status_message = StatusMessage::error("Loading saved queries failed");
+        }
+    }
+    match History::load(&rcwi_dir()) {
+        Ok(history) => app.history = history,
+        Err(err) => {
+            error!("Loading query history failed: {:?}", err);
+            app.history = History::empty(&rcwi_dir());
+            app.status_message = StatusMessage::error("Loading query history failed");
+        }
+    }
+    let app = Arc::new(Mutex::new(app));
     let (tx, rx): (Sender<AwsReq>, Receiver<AwsReq>) = std::sync::mpsc::channel();
 
     let app_r = app.clone();
@@ -99,7 +216,13 @@ fn main() -> Result<(), Box<dyn Error>> {
                     match app.selected {
                         SelectedView::LogGroups => log_groups::draw(app, f),
                         SelectedView::Overview => overview::draw(app, f),
+                        SelectedView::LogRecord => log_record::draw(app, f),
+                        SelectedView::SavedQueries => saved_queries::draw(app, f),
+                        SelectedView::History => history::draw(app, f),
+                        SelectedView::Profiles => profiles::draw(app, f),
+                        SelectedView::Tail => tail::draw(app, f),
                     };
+                    status_bar::draw_detail(app_r.lock().unwrap(), f);
                 })
                 .unwrap();
 
@@ -109,6 +232,15 @@ fn main() -> Result<(), Box<dyn Error>> {
                     let mut app = app_r.lock().unwrap();
                     if let CEvent::Key(key_code) = event {
                         match key_code.code {
+                            _ if app.status_detail_open => app.status_detail_open = false,
+                            KeyCode::Char('!')
+                                if app.mode == Mode::Normal && app.status_message.has_detail() =>
+                            {
+                                app.status_detail_open = true;
+                            }
+                            _ if app.query_editor.active => {
+                                query_editor::handle_input(app, key_code)
+                            }
                             KeyCode::Char('q') if app.mode == Mode::Normal => {
                                 app.quit = true;
                                 break;
@@ -119,6 +251,15 @@ fn main() -> Result<(), Box<dyn Error>> {
                                         log_groups::handle_input(app, k, &tx)
                                     }
                                     SelectedView::Overview => overview::handle_input(app, k, &tx),
+                                    SelectedView::LogRecord => {
+                                        log_record::handle_input(app, k, &tx)
+                                    }
+                                    SelectedView::SavedQueries => {
+                                        saved_queries::handle_input(app, k, &tx)
+                                    }
+                                    SelectedView::History => history::handle_input(app, k, &tx),
+                                    SelectedView::Profiles => profiles::handle_input(app, k, &tx),
+                                    SelectedView::Tail => tail::handle_input(app, k, &tx),
                                 };
                             }
                         }
@@ -0,0 +1,247 @@
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use serde::ser::{Serialize, SerializeMap, Serializer};
+use unicode_width::UnicodeWidthStr;
+
+use crate::overview::{result_columns, ResultRow};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum OutputFormat {
+    JsonLines,
+    Json,
+    Csv,
+    Table,
+}
+
+impl OutputFormat {
+    /// Picks the format from the extension of a file name.
+    pub(crate) fn from_path(path: &str) -> Option<Self> {
+        match Path::new(path).extension()?.to_str()?.to_lowercase().as_str() {
+            "csv" => Some(OutputFormat::Csv),
+            "json" => Some(OutputFormat::Json),
+            "ndjson" | "jsonl" => Some(OutputFormat::JsonLines),
+            "txt" => Some(OutputFormat::Table),
+            _ => None,
+        }
+    }
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "json" | "jsonl" | "ndjson" => Ok(OutputFormat::JsonLines),
+            "json-array" => Ok(OutputFormat::Json),
+            "csv" => Ok(OutputFormat::Csv),
+            "table" => Ok(OutputFormat::Table),
+            _ => Err(format!(
+                "Unknown output format '{}', expected json, json-array, csv or table",
+                s
+            )),
+        }
+    }
+}
+
+impl Serialize for ResultRow {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.fields.len()))?;
+        for (field, value) in &self.fields {
+            map.serialize_entry(field, value)?;
+        }
+        map.end()
+    }
+}
+
+/// Writes `rows` to the file at `path`, a leading `~/` is expanded to the home directory.
+pub(crate) fn write_file(path: &str, rows: &[ResultRow], format: OutputFormat) -> io::Result<()> {
+    let path = match (path.strip_prefix("~/"), home::home_dir()) {
+        (Some(rest), Some(home)) => home.join(rest),
+        _ => PathBuf::from(path),
+    };
+    let mut out = BufWriter::new(File::create(path)?);
+    write_rows(&mut out, rows, format)
+}
+
+/// Writes `rows` to `out` in the given format.
+pub(crate) fn write_rows(
+    out: &mut dyn Write,
+    rows: &[ResultRow],
+    format: OutputFormat,
+) -> io::Result<()> {
+    match format {
+        OutputFormat::JsonLines => {
+            for row in rows {
+                serde_json::to_writer(&mut *out, row)?;
+                writeln!(out)?;
+            }
+        }
+        OutputFormat::Json => {
+            serde_json::to_writer_pretty(&mut *out, rows)?;
+            writeln!(out)?;
+        }
+        OutputFormat::Csv => {
+            let columns = result_columns(rows);
+            writeln!(
+                out,
+                "{}",
+                columns.iter().map(|c| csv_field(c)).collect::<Vec<_>>().join(",")
+            )?;
+            for row in rows {
+                let fields: Vec<String> =
+                    columns.iter().map(|c| csv_field(row.get(c).unwrap_or(""))).collect();
+                writeln!(out, "{}", fields.join(","))?;
+            }
+        }
+        OutputFormat::Table => {
+            let columns = result_columns(rows);
+            let values: Vec<Vec<String>> = rows
+                .iter()
+                .map(|row| {
+                    columns.iter().map(|c| row.get(c).unwrap_or("").replace('\n', " ")).collect()
+                })
+                .collect();
+            let widths: Vec<usize> = columns
+                .iter()
+                .enumerate()
+                .map(|(i, c)| values.iter().map(|v| v[i].width()).fold(c.width(), usize::max))
+                .collect();
+            writeln!(out, "{}", table_line(&columns, &widths))?;
+            for row in values {
+                writeln!(out, "{}", table_line(&row, &widths))?;
+            }
+        }
+    }
+    out.flush()
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn table_line(values: &[String], widths: &[usize]) -> String {
+    let mut line = String::new();
+    for (i, value) in values.iter().enumerate() {
+        line.push_str(value);
+        if i < values.len() - 1 {
+            line.push_str(&" ".repeat(widths[i] - value.width() + 2));
+        }
+    }
+    line
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(fields: &[(&str, &str)]) -> ResultRow {
+        ResultRow { fields: fields.iter().map(|(f, v)| (f.to_string(), v.to_string())).collect() }
+    }
+
+    fn written(rows: &[ResultRow], format: OutputFormat) -> String {
+        let mut out = vec![];
+        write_rows(&mut out, rows, format).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn csv_fields_are_quoted_when_needed() {
+        let rows = vec![
+            row(&[("@ptr", "p1"), ("@message", "a, b"), ("level", "info")]),
+            row(&[("@ptr", "p2"), ("@message", "say \"hi\"\nbye"), ("level", "warn")]),
+        ];
+
+        assert_eq!(
+            written(&rows, OutputFormat::Csv),
+            "@message,level\n\"a, b\",info\n\"say \"\"hi\"\"\nbye\",warn\n"
+        );
+    }
+
+    #[test]
+    fn missing_fields_are_left_empty() {
+        let rows = vec![row(&[("a", "1")]), row(&[("b", "2")])];
+
+        assert_eq!(written(&rows, OutputFormat::Csv), "a,b\n1,\n,2\n");
+        assert_eq!(written(&rows, OutputFormat::Table), "a  b\n1  \n   2\n");
+        assert_eq!(written(&rows, OutputFormat::JsonLines), "{\"a\":\"1\"}\n{\"b\":\"2\"}\n");
+    }
+
+    #[test]
+    fn csv_of_no_rows_is_an_empty_header() {
+        assert_eq!(written(&[], OutputFormat::Csv), "\n");
+    }
+
+    #[test]
+    fn json_lines_hold_one_object_per_row_in_field_order() {
+        let rows = vec![
+            row(&[("@timestamp", "t1"), ("@message", "line\nbreak")]),
+            row(&[("@timestamp", "t2"), ("@message", "second")]),
+        ];
+
+        let out = written(&rows, OutputFormat::JsonLines);
+
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(
+            lines,
+            vec![
+                r#"{"@timestamp":"t1","@message":"line\nbreak"}"#,
+                r#"{"@timestamp":"t2","@message":"second"}"#,
+            ]
+        );
+    }
+
+    #[test]
+    fn json_is_one_array_of_objects() {
+        let rows = vec![row(&[("a", "1"), ("b", "x")]), row(&[("a", "2")])];
+
+        let value: serde_json::Value =
+            serde_json::from_str(&written(&rows, OutputFormat::Json)).unwrap();
+
+        assert_eq!(value, serde_json::json!([{"a": "1", "b": "x"}, {"a": "2"}]));
+    }
+
+    #[test]
+    fn table_columns_are_padded_to_the_widest_value() {
+        let rows = vec![row(&[("level", "info"), ("@message", "a\nb")]), row(&[("level", "é")])];
+
+        assert_eq!(written(&rows, OutputFormat::Table), "level  @message\ninfo   a b\né      \n");
+    }
+
+    #[test]
+    fn formats_are_picked_by_name_and_extension() {
+        let names = [
+            ("json", OutputFormat::JsonLines),
+            ("NDJSON", OutputFormat::JsonLines),
+            ("jsonl", OutputFormat::JsonLines),
+            ("json-array", OutputFormat::Json),
+            ("csv", OutputFormat::Csv),
+            ("table", OutputFormat::Table),
+        ];
+        for (name, format) in names {
+            assert_eq!(name.parse::<OutputFormat>(), Ok(format), "{}", name);
+        }
+        assert!("xml".parse::<OutputFormat>().unwrap_err().contains("'xml'"));
+
+        let paths = [
+            ("out.csv", Some(OutputFormat::Csv)),
+            ("~/results.JSON", Some(OutputFormat::Json)),
+            ("a.b.ndjson", Some(OutputFormat::JsonLines)),
+            ("rows.jsonl", Some(OutputFormat::JsonLines)),
+            ("rows.txt", Some(OutputFormat::Table)),
+            ("rows.xlsx", None),
+            ("rows", None),
+        ];
+        for (path, format) in paths {
+            assert_eq!(OutputFormat::from_path(path), format, "{}", path);
+        }
+    }
+}
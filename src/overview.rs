@@ -1,25 +1,38 @@
-use std::{io::Stdout, sync::mpsc::Sender};
+use std::{collections::HashSet, io::Stdout, sync::mpsc::Sender, time::Instant};
 
 use crate::{
-    controls_bar,
-    cwl::AwsReq,
+    chart::{self, chart_data, ChartData, ResultsView},
+    clipboard, controls_bar,
+    cwl::{
+        backend::{QueryStatistics, QueryStatus},
+        AwsReq,
+    },
+    fields,
+    histogram::{self, Histogram},
+    log_record::LogRecord,
+    output::{self, OutputFormat},
+    profiles, query_editor,
+    results_search::ResultsSearch,
     status_bar::{self, StatusMessage},
+    tabs, tail,
     time_select::{self, TimeSelector, TimeSelectorInput},
     Mode, SelectedView, Widget,
 };
-use arboard::Clipboard;
 use crossterm::event::KeyCode;
 use log::error;
+use time::UtcOffset;
 use tui::{
     backend::CrosstermBackend,
     layout::{Constraint, Direction, Layout, Rect},
-    style::{Color, Style},
+    style::{Color, Modifier, Style},
     text::{Span, Spans},
-    widgets::{Block, Borders, Clear, List, ListItem, Paragraph},
+    widgets::{Block, Borders, Clear, Paragraph, Row, Table},
     Frame,
 };
 use unicode_width::UnicodeWidthStr;
 
+const MAX_COLUMN_WIDTH: usize = 40;
+
 pub(crate) fn draw(
     app: std::sync::MutexGuard<crate::App>,
     frame: &mut Frame<CrosstermBackend<Stdout>>,
@@ -29,6 +42,7 @@ pub(crate) fn draw(
         .margin(2)
         .constraints(
             [
+                Constraint::Length(1),
                 Constraint::Length(3),
                 Constraint::Length(9),
                 Constraint::Min(1),
@@ -39,10 +53,29 @@ pub(crate) fn draw(
         )
         .split(frame.size());
 
+    let mut header = vec![
+        Span::raw("profile: "),
+        Span::styled(profiles::active_profile(&app.config), Style::default().fg(Color::Cyan)),
+        Span::raw("  region: "),
+        Span::styled(
+            app.aws_region.as_deref().unwrap_or("unknown"),
+            Style::default().fg(Color::Cyan),
+        ),
+    ];
+    if !app.config.targets.is_empty() {
+        header.push(Span::raw("  queries run in: "));
+        header.push(Span::styled(
+            profiles::target_names(&app.config.targets),
+            Style::default().fg(Color::Cyan),
+        ));
+    }
+    let header = Paragraph::new(Spans::from(header));
+    frame.render_widget(header, chunks[0]);
+
     let first_chunk = Layout::default()
         .direction(Direction::Horizontal)
         .constraints([Constraint::Min(1), Constraint::Length(50)].as_ref())
-        .split(chunks[0]);
+        .split(chunks[1]);
     let selected_log_groups_string = app.log_groups.selected_log_groups.join(", ");
     let log_groups = Paragraph::new(selected_log_groups_string.as_str())
         .style(match app.focused {
@@ -54,51 +87,74 @@ pub(crate) fn draw(
 
     time_select::draw(&app, frame, first_chunk[1]);
 
-    let log_groups = Paragraph::new(app.query.as_str())
-        .style(match app.focused {
-            Widget::Query => Style::default().fg(Color::Yellow),
+    let query_chunk = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Min(1), Constraint::Length(40)].as_ref())
+        .split(chunks[2]);
+    let query_style = match app.focused {
+        Widget::Query => Style::default().fg(Color::Yellow),
+        _ => Style::default(),
+    };
+    query_editor::draw(&app, frame, query_chunk[0], query_style);
+    let fields_style = match app.focused {
+        Widget::Fields => Style::default().fg(Color::Yellow),
+        _ => Style::default(),
+    };
+    fields::draw(&app, frame, query_chunk[1], fields_style);
+
+    let results_area = if app.show_histogram {
+        let areas = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Length(6), Constraint::Min(1)].as_ref())
+            .split(chunks[3]);
+        let histogram_style = match app.focused {
+            Widget::Histogram => Style::default().fg(Color::Yellow),
             _ => Style::default(),
-        })
-        .block(Block::default().borders(Borders::ALL).title("query"));
-    frame.render_widget(log_groups, chunks[1]);
-
-    let logs_size = (chunks[2].height - 2) as usize; //what if screen is too small?
-    let scroll_select = app.log_results.query_result_selected + 5;
-    let res_len = app.log_results.query_results.len();
-    let scroll_bounds = if res_len > logs_size { res_len - logs_size } else { 0 };
-    let (win_start, win_end) = if scroll_select <= logs_size {
-        (0usize, logs_size.min(res_len))
+        };
+        histogram::draw(&app, frame, areas[0], histogram_style);
+        areas[1]
     } else {
-        ((scroll_select - (logs_size)).min(scroll_bounds), scroll_select.min(res_len))
+        chunks[3]
     };
-    let messages: Vec<ListItem> = app.log_results.query_results[win_start..win_end]
-        .iter()
-        .enumerate()
-        .map(|(i, m)| {
-            let i = i + win_start;
-            let content = vec![Spans::from(Span::raw(format!("{}: {}", i, m.message)))];
-            ListItem::new(content).style(
-                if app.focused == Widget::LogRows
-                    && app.mode == Mode::Insert
-                    && app.log_results.query_result_selected == i
-                {
-                    Style::default().fg(Color::Red)
-                } else {
-                    Style::default()
-                },
-            )
+    let results_block = Block::default()
+        .style(match app.focused {
+            Widget::LogRows => Style::default().fg(Color::Yellow),
+            _ => Style::default(),
         })
-        .collect();
-    let messages = List::new(messages).block(
-        Block::default()
-            .style(match app.focused {
-                Widget::LogRows => Style::default().fg(Color::Yellow),
-                _ => Style::default(),
-            })
-            .borders(Borders::ALL)
-            .title("results"),
-    );
-    frame.render_widget(messages, chunks[2]);
+        .borders(Borders::ALL)
+        .title(results_title(&app.log_results));
+    match &app.log_results.chart {
+        Some(data) if app.log_results.view != ResultsView::Table => {
+            let offset = display_offset(&app);
+            chart::draw(frame, results_area, results_block, data, app.log_results.view, offset)
+        }
+        _ => draw_rows(
+            frame,
+            results_area,
+            results_block,
+            &app.log_results.query_results,
+            &app.log_results.columns,
+            app.log_results.query_result_selected,
+            (app.focused == Widget::LogRows && app.mode == Mode::Insert)
+                .then(|| app.log_results.search.matches.as_slice()),
+        ),
+    }
+    if let Some(search) = &app.log_results.search.input {
+        let area = Rect {
+            y: results_area.bottom().saturating_sub(3),
+            height: results_area.height.min(3),
+            ..results_area
+        };
+        frame.render_widget(Clear, area);
+        let input = Paragraph::new(search.as_str()).block(
+            Block::default()
+                .style(Style::default().fg(Color::Yellow))
+                .borders(Borders::ALL)
+                .title("search (text or /regex/)"),
+        );
+        frame.render_widget(input, area);
+        frame.set_cursor(area.x + search.width() as u16 + 1, area.y + 1);
+    }
 
     if app.time_selector.popup {
         let centered_rect = centered_rect(20, 20, frame.size());
@@ -142,9 +198,26 @@ pub(crate) fn draw(
             )
         }
     }
-    status_bar::draw(app, frame, chunks[3]);
+    if let Some(export_path) = &app.log_results.export_path {
+        let area = centered_rect(50, 20, frame.size());
+        frame.render_widget(Clear, area);
+        let block = Block::default()
+            .style(Style::default().fg(Color::Yellow))
+            .title("Export results (.csv, .json or .ndjson)")
+            .borders(Borders::ALL);
+        let input_area = Layout::default()
+            .margin(1)
+            .constraints([Constraint::Length(3)].as_ref())
+            .split(block.inner(area))[0];
+        frame.render_widget(block, area);
+        let input = Paragraph::new(export_path.as_str())
+            .block(Block::default().borders(Borders::ALL).title("file"));
+        frame.render_widget(input, input_area);
+        frame.set_cursor(input_area.x + export_path.width() as u16 + 1, input_area.y + 1);
+    }
+    status_bar::draw(app, frame, chunks[4]);
 
-    controls_bar::draw(frame, chunks[4]);
+    controls_bar::draw(frame, chunks[5]);
 }
 
 pub(crate) fn handle_input(
@@ -152,6 +225,14 @@ pub(crate) fn handle_input(
     key_code: KeyCode,
     cwl: &Sender<AwsReq>,
 ) {
+    if app.log_results.export_path.is_some() {
+        handle_export_input(app, key_code);
+        return;
+    }
+    if app.log_results.search.input.is_some() {
+        handle_search_input(app, key_code);
+        return;
+    }
     match app.time_selector.popup {
         true => match key_code {
             KeyCode::Backspace => match app.time_selector.input {
@@ -202,9 +283,10 @@ pub(crate) fn handle_input(
                             cwl.send(AwsReq::ListLogGroups).unwrap();
                         }
                     }
+                    Widget::Query if app.config.builtin_editor => query_editor::open(&mut app),
                     Widget::Query => app.break_inner = true,
                     Widget::TimeSelector => app.time_selector.popup = true,
-                    Widget::LogRows => app.mode = Mode::Insert,
+                    Widget::LogRows | Widget::Fields | Widget::Histogram => app.mode = Mode::Insert,
                     _ => {}
                 },
                 KeyCode::Char('h') | KeyCode::Left => match app.focused {
@@ -214,13 +296,22 @@ pub(crate) fn handle_input(
                     Widget::TimeSelector => {
                         app.focused = Widget::LogGroups;
                     }
+                    Widget::Query => {
+                        app.focused = Widget::Fields;
+                    }
+                    Widget::Fields => {
+                        app.focused = Widget::Query;
+                    }
                     _ => {}
                 },
                 KeyCode::Char('j') | KeyCode::Down => match app.focused {
                     Widget::LogGroups | Widget::TimeSelector => {
                         app.focused = Widget::Query;
                     }
-                    Widget::Query => {
+                    Widget::Query | Widget::Fields if app.show_histogram => {
+                        app.focused = Widget::Histogram;
+                    }
+                    Widget::Query | Widget::Fields | Widget::Histogram => {
                         app.focused = Widget::LogRows;
                     }
                     _ => {
@@ -234,10 +325,16 @@ pub(crate) fn handle_input(
                     Widget::Query => {
                         app.focused = Widget::LogGroups;
                     }
+                    Widget::Fields => {
+                        app.focused = Widget::TimeSelector;
+                    }
                     Widget::TimeSelector => {
                         app.focused = Widget::LogRows;
                     }
-                    Widget::LogRows => {
+                    Widget::LogRows if app.show_histogram => {
+                        app.focused = Widget::Histogram;
+                    }
+                    Widget::LogRows | Widget::Histogram => {
                         app.focused = Widget::Query;
                     }
                     _ => {
@@ -251,58 +348,137 @@ pub(crate) fn handle_input(
                     Widget::TimeSelector => {
                         app.focused = Widget::LogGroups;
                     }
+                    Widget::Query => {
+                        app.focused = Widget::Fields;
+                    }
+                    Widget::Fields => {
+                        app.focused = Widget::Query;
+                    }
                     _ => {}
                 },
                 KeyCode::Char('r') => {
-                    cwl.send(AwsReq::RunQuery).unwrap();
+                    cwl.send(AwsReq::RunQuery(app.tabs.active_id())).unwrap();
+                }
+                KeyCode::Char('s') => {
+                    app.selected = SelectedView::SavedQueries;
+                }
+                KeyCode::Char('H') => {
+                    app.selected = SelectedView::History;
+                }
+                KeyCode::Char('i') => query_editor::open(&mut app),
+                KeyCode::Char('E') => app.break_inner = true,
+                KeyCode::Char('P') => profiles::open(&mut app),
+                KeyCode::Char('t') => tail::open(&mut app, cwl),
+                KeyCode::Char('e') => {
+                    app.log_results.export_path = Some(String::new());
+                    app.mode = Mode::Insert;
+                }
+                KeyCode::Char('v') => {
+                    app.log_results.view = app.log_results.view.next();
+                    if app.log_results.chart.is_none() {
+                        app.status_message = StatusMessage::info(
+                            "Only results of stats ... by bin() queries can be charted",
+                        );
+                    }
+                }
+                KeyCode::Char('g') => {
+                    app.show_histogram = !app.show_histogram;
+                    if !app.show_histogram && app.focused == Widget::Histogram {
+                        app.focused = Widget::LogRows;
+                    }
+                }
+                KeyCode::Char('T') => tabs::open(&mut app),
+                KeyCode::Char('X') => tabs::close(&mut app, cwl),
+                KeyCode::Char('[') => {
+                    let index = app.tabs.active_index() as isize - 1;
+                    tabs::switch(&mut app, index, cwl);
+                }
+                KeyCode::Char(']') => {
+                    let index = app.tabs.active_index() as isize + 1;
+                    tabs::switch(&mut app, index, cwl);
+                }
+                KeyCode::Char('c') if app.log_results.is_running() => {
+                    app.log_results.cancel_query = true;
+                    app.status_message =
+                        StatusMessage::info("Cancelling Cloudwatch Insights query");
                 }
 
                 _ => {}
             },
             Mode::Insert => match app.focused {
+                Widget::Fields => fields::handle_input(app, key_code),
+                Widget::Histogram => histogram::handle_input(app, key_code, cwl),
                 Widget::LogRows => match key_code {
                     KeyCode::Esc => {
                         app.mode = Mode::Normal;
                     }
                     KeyCode::Char('j') | KeyCode::Down => {
                         let len = app.log_results.query_results.len();
-                        app.log_results.query_result_selected = if len > 0 {
+                        let selected = if len > 0 {
                             (app.log_results.query_result_selected + 1) % len
                         } else {
                             0
                         };
+                        app.log_results.select(selected);
                     }
                     KeyCode::Char('k') | KeyCode::Up => {
                         let l = app.log_results.query_results.len();
                         let r = app.log_results.query_result_selected;
-                        app.log_results.query_result_selected = if r > 0 && l > 0 {
+                        let selected = if r > 0 && l > 0 {
                             (r - 1) % l
                         } else if r == 0 && l > 0 {
                             l - 1
                         } else {
                             0
                         };
+                        app.log_results.select(selected);
+                    }
+                    KeyCode::Char('/') => {
+                        app.log_results.search.input = Some(app.log_results.search.pattern.clone());
+                    }
+                    KeyCode::Char(c @ ('n' | 'N')) => {
+                        let log_results = &app.log_results;
+                        let selected = log_results.query_result_selected;
+                        let next = if c == 'n' {
+                            log_results.search.next(selected)
+                        } else {
+                            log_results.search.previous(selected)
+                        };
+                        match next {
+                            Some(next) => app.log_results.select(next),
+                            None if log_results.search.pattern.is_empty() => {
+                                app.status_message =
+                                    StatusMessage::info("Search the results with / first")
+                            }
+                            None => {
+                                app.status_message = StatusMessage::error(&format!(
+                                    "No rows match '{}'",
+                                    log_results.search.pattern
+                                ))
+                            }
+                        }
                     }
                     KeyCode::Char('y') => {
                         if app.log_results.query_results.len() < 1 {
                             return;
                         }
-                        let content = &app.log_results.query_results
-                            [app.log_results.query_result_selected]
-                            .message;
-                        let cb = Clipboard::new();
-                        if let Ok(mut cb) = cb {
-                            let res = cb.set_text(content.clone());
-                            if let Err(err) = res {
-                                error!("Clipboard action failed: {:?}", err);
-                                app.status_message =
-                                    StatusMessage::error("Clipboard action failed.");
-                            } else {
-                                app.status_message = StatusMessage::info("Yanked to clipboard.");
-                            }
-                        } else if let Err(err) = cb {
-                            error!("Clipboard action failed: {:?}", err);
-                            app.status_message = StatusMessage::error("Clipboard action failed.");
+                        let row =
+                            &app.log_results.query_results[app.log_results.query_result_selected];
+                        app.status_message = yank_row(row, &app.log_results.columns);
+                    }
+                    KeyCode::Enter => {
+                        let ptr = app
+                            .log_results
+                            .selected_row()
+                            .and_then(|row| row.get("@ptr"))
+                            .map(String::from);
+                        if let Some(ptr) = ptr {
+                            app.log_record = LogRecord::default();
+                            app.selected = SelectedView::LogRecord;
+                            cwl.send(AwsReq::GetLogRecord(ptr)).unwrap();
+                        } else {
+                            app.status_message =
+                                StatusMessage::error("Row has no @ptr to look up the log record");
                         }
                     }
                     _ => {}
@@ -313,7 +489,231 @@ pub(crate) fn handle_input(
     }
 }
 
-fn centered_rect(percent_x: u16, percent_y: u16, r: Rect) -> Rect {
+fn handle_export_input(mut app: std::sync::MutexGuard<crate::App>, key_code: KeyCode) {
+    let export_path = match app.log_results.export_path.as_mut() {
+        Some(export_path) => export_path,
+        None => return,
+    };
+    match key_code {
+        KeyCode::Esc => {
+            app.log_results.export_path = None;
+            app.mode = Mode::Normal;
+        }
+        KeyCode::Backspace => {
+            export_path.pop();
+        }
+        KeyCode::Char(c) => export_path.push(c),
+        KeyCode::Enter => {
+            let path = export_path.clone();
+            let format = match OutputFormat::from_path(&path) {
+                Some(format) => format,
+                None => {
+                    app.status_message =
+                        StatusMessage::error("Unknown file extension, use .csv, .json or .ndjson");
+                    return;
+                }
+            };
+            match output::write_file(&path, &app.log_results.query_results, format) {
+                Ok(()) => {
+                    app.status_message = StatusMessage::info(&format!(
+                        "Exported {} rows to {}",
+                        app.log_results.query_results.len(),
+                        path
+                    ));
+                }
+                Err(err) => {
+                    error!("Exporting results failed: {:?}", err);
+                    app.status_message =
+                        StatusMessage::error(&format!("Exporting results failed: {}", err));
+                }
+            }
+            app.log_results.export_path = None;
+            app.mode = Mode::Normal;
+        }
+        _ => {}
+    }
+}
+
+fn handle_search_input(mut app: std::sync::MutexGuard<crate::App>, key_code: KeyCode) {
+    let input = match app.log_results.search.input.as_mut() {
+        Some(input) => input,
+        None => return,
+    };
+    match key_code {
+        KeyCode::Esc => app.log_results.search.input = None,
+        KeyCode::Backspace => {
+            input.pop();
+        }
+        KeyCode::Char(c) => input.push(c),
+        KeyCode::Enter => {
+            let pattern = input.clone();
+            let log_results = &mut app.log_results;
+            if let Err(err) = log_results.search.search(&pattern, &log_results.query_results) {
+                app.status_message = StatusMessage::error(&err);
+                return;
+            }
+            log_results.search.input = None;
+            let selected = log_results.query_result_selected;
+            let matches = log_results.search.matches.len();
+            match log_results.search.next(selected) {
+                Some(next) => {
+                    log_results.select(next);
+                    app.status_message =
+                        StatusMessage::info(&format!("{} rows match '{}'", matches, pattern));
+                }
+                None if pattern.is_empty() => {}
+                None => {
+                    app.status_message =
+                        StatusMessage::error(&format!("No rows match '{}'", pattern))
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+fn results_title(log_results: &LogResults) -> String {
+    let progress = match &log_results.progress {
+        Some(progress) => progress,
+        None => return "results".to_string(),
+    };
+    let elapsed = progress.finished.unwrap_or_else(Instant::now) - progress.started;
+    let rows = match progress.finished {
+        None => format!("{} rows", log_results.query_results.len()),
+        Some(_) if log_results.capped => {
+            format!("{} rows, capped", log_results.query_results.len())
+        }
+        Some(_) => format!("{} rows, complete", log_results.query_results.len()),
+    };
+    let mut rows = if log_results.new_rows > 0 && log_results.query_result_selected > 0 {
+        format!("{}, {} new above", rows, log_results.new_rows)
+    } else {
+        rows
+    };
+    if !log_results.search.pattern.is_empty() {
+        rows += &format!(
+            ", {} matching '{}'",
+            log_results.search.matches.len(),
+            log_results.search.pattern
+        );
+    }
+    format!(
+        "results ({}, {:.1}s, {}, {} matched, {} scanned, {})",
+        progress.status,
+        elapsed.as_secs_f64(),
+        rows,
+        progress.statistics.records_matched,
+        progress.statistics.records_scanned,
+        format_bytes(progress.statistics.bytes_scanned),
+    )
+}
+
+fn format_bytes(bytes: f64) -> String {
+    let units = ["B", "kB", "MB", "GB", "TB"];
+    let mut value = bytes;
+    let mut unit = 0;
+    while value >= 1000.0 && unit < units.len() - 1 {
+        value /= 1000.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} {}", value, units[unit])
+    } else {
+        format!("{:.1} {}", value, units[unit])
+    }
+}
+
+/// Renders `rows` as a table with a row number column, scrolled so `selected` is visible.
+/// With `highlight` set the selected row is highlighted and so are the rows it lists, the
+/// matches of a search.
+pub(crate) fn draw_rows(
+    frame: &mut Frame<CrosstermBackend<Stdout>>,
+    area: Rect,
+    block: Block,
+    rows: &[ResultRow],
+    columns: &[String],
+    selected: usize,
+    highlight: Option<&[usize]>,
+) {
+    let logs_size = area.height.saturating_sub(3) as usize;
+    let (win_start, win_end) = scroll_window(selected, rows.len(), logs_size);
+    let visible_rows = &rows[win_start..win_end];
+    let mut col_widths: Vec<usize> =
+        columns.iter().map(|c| c.width().min(MAX_COLUMN_WIDTH)).collect();
+    for row in visible_rows {
+        for (i, column) in columns.iter().enumerate() {
+            let value_width = row.get(column).map(|v| v.width()).unwrap_or(0);
+            col_widths[i] = col_widths[i].max(value_width).min(MAX_COLUMN_WIDTH);
+        }
+    }
+    let index_width = rows.len().to_string().len().max(1);
+    let mut widths = vec![Constraint::Length(index_width as u16)];
+    widths.extend(col_widths.iter().enumerate().map(|(i, w)| {
+        if i == col_widths.len() - 1 {
+            Constraint::Min(*w as u16)
+        } else {
+            Constraint::Length(*w as u16)
+        }
+    }));
+
+    let header = Row::new(
+        std::iter::once("#".to_string()).chain(columns.iter().cloned()).collect::<Vec<_>>(),
+    )
+    .style(Style::default().add_modifier(Modifier::BOLD));
+    let table_rows: Vec<Row> = visible_rows
+        .iter()
+        .enumerate()
+        .map(|(i, m)| {
+            let i = i + win_start;
+            let cells = std::iter::once(i.to_string())
+                .chain(columns.iter().map(|c| m.get(c).unwrap_or("").replace('\n', " ")))
+                .collect::<Vec<_>>();
+            Row::new(cells).style(match highlight {
+                Some(_) if selected == i => Style::default().fg(Color::Red),
+                Some(matches) if matches.binary_search(&i).is_ok() => {
+                    Style::default().fg(Color::Cyan)
+                }
+                _ => Style::default(),
+            })
+        })
+        .collect();
+    let table =
+        Table::new(table_rows).header(header).widths(&widths).column_spacing(1).block(block);
+    frame.render_widget(table, area);
+}
+
+/// Copies the `@message` of a row to the clipboard, or all `columns` tab separated when the
+/// row has no message.
+pub(crate) fn yank_row(row: &ResultRow, columns: &[String]) -> StatusMessage {
+    let content = match row.get("@message") {
+        Some(message) => message.to_string(),
+        None => columns.iter().map(|c| row.get(c).unwrap_or("")).collect::<Vec<_>>().join("\t"),
+    };
+    clipboard::yank(content)
+}
+
+/// The offset times are shown in, that of the selected time range when it was given with one,
+/// otherwise the configured timezone.
+pub(crate) fn display_offset(app: &crate::App) -> UtcOffset {
+    app.time_selector
+        .utc_offset()
+        .or_else(|| app.config.utc_offset().ok())
+        .unwrap_or(UtcOffset::UTC)
+}
+
+/// Returns the range of rows to show in a list of `len` rows that is `height` rows high,
+/// keeping some rows visible below the selected one.
+pub(crate) fn scroll_window(selected: usize, len: usize, height: usize) -> (usize, usize) {
+    let scroll_select = selected + 5;
+    let scroll_bounds = if len > height { len - height } else { 0 };
+    if scroll_select <= height {
+        (0usize, height.min(len))
+    } else {
+        ((scroll_select - (height)).min(scroll_bounds), scroll_select.min(len))
+    }
+}
+
+pub(crate) fn centered_rect(percent_x: u16, percent_y: u16, r: Rect) -> Rect {
     let popup_layout = Layout::default()
         .direction(Direction::Vertical)
         .constraints(
@@ -340,17 +740,322 @@ fn centered_rect(percent_x: u16, percent_y: u16, r: Rect) -> Rect {
 }
 
 pub(crate) struct LogResults {
-    pub(crate) query_results: Vec<QueryLogRow>,
+    pub(crate) query_results: Vec<ResultRow>,
+    pub(crate) columns: Vec<String>,
     query_result_selected: usize,
+    /// File name being typed in the export prompt, `None` when the prompt is closed.
+    export_path: Option<String>,
+    pub(crate) progress: Option<QueryProgress>,
+    /// Id of the query running for these results, in the first target it was started in.
+    pub(crate) running_query_id: Option<String>,
+    /// A query is being started for these results and has no id yet.
+    pub(crate) starting: bool,
+    /// Asks the worker to stop the running query.
+    pub(crate) cancel_query: bool,
+    /// The results as a time series, `None` unless they came from a `stats ... by bin()` query.
+    pub(crate) chart: Option<ChartData>,
+    /// Column of the bins the running query groups by, see [`chart::bin_column`].
+    pub(crate) bin_column: Option<String>,
+    pub(crate) view: ResultsView,
+    /// Events matching the query per bin, filled by a separate query next to the query itself.
+    pub(crate) histogram: Option<Histogram>,
+    /// Some rows were left out because a query, or one of its time windows, hit the row limit.
+    pub(crate) capped: bool,
+    /// Rows that arrived above the selected row since it was last at the top.
+    pub(crate) new_rows: usize,
+    /// `@ptr` of every row in `query_results`.
+    known_ptrs: HashSet<String>,
+    pub(crate) search: ResultsSearch,
+}
+
+/// Status and statistics of the latest query run, updated on every poll.
+pub(crate) struct QueryProgress {
+    pub(crate) status: QueryStatus,
+    pub(crate) statistics: QueryStatistics,
+    pub(crate) started: Instant,
+    pub(crate) finished: Option<Instant>,
+}
+
+impl QueryProgress {
+    pub(crate) fn start() -> Self {
+        Self {
+            status: QueryStatus::Scheduled,
+            statistics: QueryStatistics::default(),
+            started: Instant::now(),
+            finished: None,
+        }
+    }
+
+    pub(crate) fn finish(&mut self, status: QueryStatus) {
+        self.status = status;
+        self.finished = Some(Instant::now());
+    }
 }
 impl Default for LogResults {
     fn default() -> Self {
-        Self { query_results: vec![], query_result_selected: 0usize }
+        Self {
+            query_results: vec![],
+            columns: vec![],
+            query_result_selected: 0usize,
+            export_path: None,
+            progress: None,
+            running_query_id: None,
+            starting: false,
+            cancel_query: false,
+            chart: None,
+            bin_column: None,
+            view: ResultsView::Line,
+            histogram: None,
+            capped: false,
+            new_rows: 0,
+            known_ptrs: HashSet::new(),
+            search: ResultsSearch::default(),
+        }
     }
 }
 
-pub(crate) struct QueryLogRow {
-    pub(crate) message: String,
-    pub(crate) timestamp: String,
-    pub(crate) ptr: String,
+impl LogResults {
+    /// Whether a query is running for these results or being started.
+    pub(crate) fn is_running(&self) -> bool {
+        self.starting || self.running_query_id.is_some()
+    }
+
+    /// Merges the latest results of a query into the rows shown, keyed by `@ptr`.
+    ///
+    /// Rows are only ever added while a query runs and keep their order, so only the rows with
+    /// an unknown `@ptr` are added between the rows shown. A selection at the top stays there to
+    /// follow the newest rows, otherwise it stays on the same row and the rows inserted above it
+    /// are counted as new. Results with rows without `@ptr`, like those of `stats` queries,
+    /// replace the rows shown and keep the index of the selection.
+    pub(crate) fn set_results(&mut self, rows: Vec<ResultRow>) {
+        if rows.iter().any(|row| row.get("@ptr").is_none()) {
+            self.replace_results(rows);
+            return;
+        }
+        if rows.iter().all(|row| self.known_ptrs.contains(row.get("@ptr").unwrap_or_default())) {
+            return;
+        }
+        // Built in one pass, inserting into the rows shown would move them for every new row.
+        let selected = self.query_result_selected;
+        let mut merged = Vec::with_capacity(rows.len().max(self.query_results.len()));
+        // Where every row shown before ends up.
+        let mut moved = Vec::with_capacity(self.query_results.len());
+        let mut added = vec![];
+        let mut old = std::mem::take(&mut self.query_results).into_iter();
+        for row in rows {
+            let ptr = row.get("@ptr").unwrap_or_default();
+            if !self.known_ptrs.contains(ptr) {
+                if selected > 0 && moved.len() <= selected {
+                    self.new_rows += 1;
+                }
+                self.known_ptrs.insert(ptr.to_string());
+                added.push(merged.len());
+                merged.push(row);
+                continue;
+            }
+            for old_row in old.by_ref() {
+                let found = old_row.get("@ptr") == Some(ptr);
+                moved.push(merged.len());
+                merged.push(old_row);
+                if found {
+                    break;
+                }
+            }
+        }
+        for old_row in old {
+            moved.push(merged.len());
+            merged.push(old_row);
+        }
+        self.query_results = merged;
+        if selected > 0 {
+            self.query_result_selected = moved.get(selected).copied().unwrap_or(0);
+        }
+
+        for i in &added {
+            for (field, _) in &self.query_results[*i].fields {
+                if field != "@ptr" && !self.columns.contains(field) {
+                    self.columns.push(field.clone());
+                }
+            }
+        }
+        self.chart = self
+            .bin_column
+            .as_deref()
+            .and_then(|bin| chart_data(&self.query_results, &self.columns, bin));
+        self.search.rows_added(&moved, &added, &self.query_results);
+    }
+
+    fn replace_results(&mut self, rows: Vec<ResultRow>) {
+        if rows.len() == self.query_results.len()
+            && rows.iter().zip(&self.query_results).all(|(new, old)| new.fields == old.fields)
+        {
+            return;
+        }
+        self.known_ptrs = rows.iter().filter_map(|row| row.get("@ptr")).map(String::from).collect();
+        self.columns = result_columns(&rows);
+        self.chart =
+            self.bin_column.as_deref().and_then(|bin| chart_data(&rows, &self.columns, bin));
+        self.query_results = rows;
+        self.search.update(&self.query_results);
+        if self.query_result_selected >= self.query_results.len() {
+            self.select(0);
+        }
+    }
+
+    /// Drops the rows of the previous query, before a new one starts.
+    pub(crate) fn clear(&mut self) {
+        self.replace_results(vec![]);
+        self.select(0);
+    }
+
+    pub(crate) fn selected_row(&self) -> Option<&ResultRow> {
+        self.query_results.get(self.query_result_selected)
+    }
+
+    /// Selects the row at `index`, back at the top all rows have been seen.
+    pub(crate) fn select(&mut self, index: usize) {
+        self.query_result_selected = index;
+        if index == 0 {
+            self.new_rows = 0;
+        }
+    }
+}
+
+/// Collects the fields of `rows` in the order they first appear. `@ptr` is only used to
+/// look up the underlying log record and is never shown.
+pub(crate) fn result_columns(rows: &[ResultRow]) -> Vec<String> {
+    let mut columns: Vec<String> = vec![];
+    for row in rows {
+        for (field, _) in &row.fields {
+            if field != "@ptr" && !columns.contains(field) {
+                columns.push(field.clone());
+            }
+        }
+    }
+    columns
+}
+
+/// A single row of query results, holding the fields in the order they were returned.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct ResultRow {
+    pub(crate) fields: Vec<(String, String)>,
+}
+
+impl ResultRow {
+    pub(crate) fn get(&self, field: &str) -> Option<&str> {
+        self.fields.iter().find(|(f, _)| f == field).map(|(_, v)| v.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(fields: &[(&str, &str)]) -> ResultRow {
+        ResultRow { fields: fields.iter().map(|(f, v)| (f.to_string(), v.to_string())).collect() }
+    }
+
+    fn ptrs(log_results: &LogResults) -> Vec<&str> {
+        log_results.query_results.iter().map(|r| r.get("@ptr").unwrap()).collect()
+    }
+
+    #[test]
+    fn rows_with_new_ptrs_are_inserted_in_order() {
+        let mut log_results = LogResults::default();
+        log_results.set_results(vec![row(&[("@ptr", "4")]), row(&[("@ptr", "2")])]);
+
+        log_results.set_results(vec![
+            row(&[("@ptr", "5"), ("level", "info")]),
+            row(&[("@ptr", "4"), ("@message", "changed")]),
+            row(&[("@ptr", "3")]),
+            row(&[("@ptr", "2")]),
+            row(&[("@ptr", "1")]),
+        ]);
+
+        assert_eq!(ptrs(&log_results), vec!["5", "4", "3", "2", "1"]);
+        assert_eq!(log_results.query_results[1].get("@message"), None);
+        assert_eq!(log_results.columns, vec!["level"]);
+    }
+
+    #[test]
+    fn rows_of_many_windows_are_merged_in_order() {
+        let ptr_rows = |ptrs: &mut dyn Iterator<Item = usize>| -> Vec<ResultRow> {
+            ptrs.map(|p| row(&[("@ptr", &p.to_string())])).collect()
+        };
+        let mut log_results = LogResults::default();
+        log_results.set_results(ptr_rows(&mut (0..25_000).rev().filter(|p| p % 2 == 0)));
+
+        log_results.set_results(ptr_rows(&mut (0..25_000).rev()));
+
+        assert_eq!(log_results.query_results.len(), 25_000);
+        let expected: Vec<String> = (0..25_000).rev().map(|p| p.to_string()).collect();
+        assert_eq!(ptrs(&log_results), expected);
+    }
+
+    #[test]
+    fn selected_row_is_kept_while_new_rows_arrive() {
+        let mut log_results = LogResults::default();
+        log_results.set_results(vec![row(&[("@ptr", "2")]), row(&[("@ptr", "1")])]);
+        log_results.select(1);
+
+        log_results.set_results(vec![
+            row(&[("@ptr", "4")]),
+            row(&[("@ptr", "3")]),
+            row(&[("@ptr", "2")]),
+            row(&[("@ptr", "1")]),
+            row(&[("@ptr", "0")]),
+        ]);
+
+        assert_eq!(log_results.selected_row().unwrap().get("@ptr"), Some("1"));
+        assert_eq!(log_results.new_rows, 2);
+        log_results.select(0);
+        assert_eq!(log_results.new_rows, 0);
+    }
+
+    #[test]
+    fn selection_at_the_top_follows_the_newest_rows() {
+        let mut log_results = LogResults::default();
+        log_results.set_results(vec![row(&[("@ptr", "1")])]);
+
+        log_results.set_results(vec![row(&[("@ptr", "2")]), row(&[("@ptr", "1")])]);
+
+        assert_eq!(log_results.selected_row().unwrap().get("@ptr"), Some("2"));
+        assert_eq!(log_results.new_rows, 0);
+    }
+
+    #[test]
+    fn search_matches_move_with_inserted_rows() {
+        let mut log_results = LogResults::default();
+        log_results.set_results(vec![
+            row(&[("@ptr", "3"), ("@message", "error")]),
+            row(&[("@ptr", "1"), ("@message", "ok")]),
+        ]);
+        log_results.search.search("error", &log_results.query_results).unwrap();
+
+        log_results.set_results(vec![
+            row(&[("@ptr", "4"), ("@message", "ok")]),
+            row(&[("@ptr", "3"), ("@message", "error")]),
+            row(&[("@ptr", "2"), ("@message", "another error")]),
+            row(&[("@ptr", "1"), ("@message", "ok")]),
+        ]);
+
+        assert_eq!(log_results.search.matches, vec![1, 2]);
+    }
+
+    #[test]
+    fn rows_without_ptr_replace_the_results() {
+        let mut log_results = LogResults::default();
+        log_results.set_results(vec![row(&[("count(*)", "1")]), row(&[("count(*)", "2")])]);
+        log_results.select(1);
+
+        log_results.set_results(vec![row(&[("count(*)", "3")]), row(&[("count(*)", "4")])]);
+
+        let counts: Vec<_> =
+            log_results.query_results.iter().map(|r| r.get("count(*)").unwrap()).collect();
+        assert_eq!(counts, vec!["3", "4"]);
+        assert_eq!(log_results.selected_row().unwrap().get("count(*)"), Some("4"));
+        log_results.clear();
+        assert!(log_results.query_results.is_empty());
+        assert!(log_results.selected_row().is_none());
+    }
 }
@@ -0,0 +1,377 @@
+use std::{
+    env, fs, io,
+    io::Stdout,
+    path::{Path, PathBuf},
+    sync::mpsc::Sender,
+};
+
+use crate::{
+    config::{Config, QueryTarget},
+    cwl::AwsReq,
+    overview::scroll_window,
+    status_bar::{self, StatusMessage},
+    SelectedView, Widget,
+};
+use crossterm::event::KeyCode;
+use log::error;
+use tui::{
+    backend::CrosstermBackend,
+    layout::{Constraint, Direction, Layout, Rect},
+    style::{Color, Style},
+    text::{Span, Spans},
+    widgets::{Block, Borders, List, ListItem, Paragraph},
+    Frame,
+};
+
+const DEFAULT_PROFILE: &str = "default";
+/// Shown first in the region list, keeps the region configured for the profile.
+const PROFILE_REGION: &str = "(profile default)";
+/// Regions offered in the picker, the commercial regions listed under "Regional endpoints" in
+/// the AWS General Reference. Add new regions there as AWS launches them, until then they can
+/// still be set with `region` in the config file.
+const REGIONS: &[&str] = &[
+    "us-east-1",
+    "us-east-2",
+    "us-west-1",
+    "us-west-2",
+    "af-south-1",
+    "ap-east-1",
+    "ap-south-1",
+    "ap-northeast-1",
+    "ap-northeast-2",
+    "ap-northeast-3",
+    "ap-southeast-1",
+    "ap-southeast-2",
+    "ca-central-1",
+    "eu-central-1",
+    "eu-west-1",
+    "eu-west-2",
+    "eu-west-3",
+    "eu-south-1",
+    "eu-north-1",
+    "me-south-1",
+    "sa-east-1",
+];
+
+#[derive(Default)]
+pub(crate) struct Profiles {
+    profiles: Vec<String>,
+    selected_profile: usize,
+    selected_region: usize,
+    regions_focused: bool,
+}
+
+/// The profile used for new clients, the config takes precedence over `AWS_PROFILE`.
+pub(crate) fn active_profile(config: &Config) -> String {
+    config
+        .profile
+        .clone()
+        .or_else(|| env::var("AWS_PROFILE").ok())
+        .unwrap_or_else(|| DEFAULT_PROFILE.to_string())
+}
+
+fn aws_file(env_var: &str, name: &str) -> Option<PathBuf> {
+    env::var_os(env_var)
+        .map(PathBuf::from)
+        .or_else(|| home::home_dir().map(|home| home.join(".aws").join(name)))
+}
+
+/// Section names of an ini style AWS file, `prefix` is stripped and sections without it skipped.
+/// Comments after a section header are ignored, commented out headers are skipped.
+fn read_sections(path: &Path, prefix: &str) -> io::Result<Vec<String>> {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(err) => return Err(err),
+    };
+    Ok(content
+        .lines()
+        .filter_map(|line| line.trim().strip_prefix('[')?.split_once(']').map(|(s, _)| s))
+        .filter_map(|section| match section.trim() {
+            DEFAULT_PROFILE => Some(DEFAULT_PROFILE.to_string()),
+            section => section.strip_prefix(prefix).map(|name| name.trim().to_string()),
+        })
+        .collect())
+}
+
+/// Profiles from the shared config and credentials files, `default` first and the rest sorted.
+fn load_profiles() -> io::Result<Vec<String>> {
+    profiles_from(
+        aws_file("AWS_CONFIG_FILE", "config").as_deref(),
+        aws_file("AWS_SHARED_CREDENTIALS_FILE", "credentials").as_deref(),
+    )
+}
+
+/// Profiles from a config file, where they are named `[profile name]`, and a credentials file,
+/// where they are named `[name]`. Profiles in both are listed once.
+fn profiles_from(config: Option<&Path>, credentials: Option<&Path>) -> io::Result<Vec<String>> {
+    let mut profiles = vec![];
+    if let Some(path) = config {
+        profiles.extend(read_sections(path, "profile ")?);
+    }
+    if let Some(path) = credentials {
+        profiles.extend(read_sections(path, "")?);
+    }
+    profiles.sort_by_key(|p| (p != DEFAULT_PROFILE, p.clone()));
+    profiles.dedup();
+    Ok(profiles)
+}
+
+/// Switches to the picker with the active profile and region preselected.
+pub(crate) fn open(app: &mut std::sync::MutexGuard<crate::App>) {
+    let profiles = match load_profiles() {
+        Ok(profiles) => profiles,
+        Err(err) => {
+            error!("Reading AWS profiles failed: {:?}", err);
+            app.status_message = StatusMessage::error("Reading AWS profiles failed");
+            return;
+        }
+    };
+    let active = active_profile(&app.config);
+    app.profiles = Profiles {
+        selected_profile: profiles.iter().position(|p| *p == active).unwrap_or(0),
+        selected_region: app
+            .config
+            .region
+            .as_ref()
+            .and_then(|r| REGIONS.iter().position(|region| region == r))
+            .map(|i| i + 1)
+            .unwrap_or(0),
+        profiles,
+        regions_focused: false,
+    };
+    app.selected = SelectedView::Profiles;
+}
+
+fn region_names() -> Vec<&'static str> {
+    std::iter::once(PROFILE_REGION).chain(REGIONS.iter().copied()).collect()
+}
+
+fn draw_list(
+    frame: &mut Frame<CrosstermBackend<Stdout>>,
+    area: Rect,
+    items: &[&str],
+    selected: usize,
+    focused: bool,
+    title: &str,
+) {
+    let height = area.height.saturating_sub(2) as usize;
+    let (win_start, win_end) = scroll_window(selected, items.len(), height);
+    let items: Vec<ListItem> = items[win_start..win_end]
+        .iter()
+        .enumerate()
+        .map(|(i, item)| {
+            ListItem::new(Spans::from(Span::raw(*item))).style(if i + win_start == selected {
+                Style::default().fg(Color::Red)
+            } else {
+                Style::default()
+            })
+        })
+        .collect();
+    let list = List::new(items).block(
+        Block::default()
+            .style(if focused { Style::default().fg(Color::Yellow) } else { Style::default() })
+            .borders(Borders::ALL)
+            .title(title),
+    );
+    frame.render_widget(list, area);
+}
+
+pub(crate) fn draw(
+    app: std::sync::MutexGuard<crate::App>,
+    frame: &mut Frame<CrosstermBackend<Stdout>>,
+) {
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(2)
+        .constraints(
+            [
+                Constraint::Min(1),
+                Constraint::Length(3),
+                Constraint::Length(3),
+                Constraint::Length(2),
+            ]
+            .as_ref(),
+        )
+        .split(frame.size());
+    let columns = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(50), Constraint::Percentage(50)].as_ref())
+        .split(chunks[0]);
+
+    let picker = &app.profiles;
+    let profiles: Vec<&str> = picker.profiles.iter().map(|p| p.as_str()).collect();
+    draw_list(
+        frame,
+        columns[0],
+        &profiles,
+        picker.selected_profile,
+        !picker.regions_focused,
+        "profiles",
+    );
+    draw_list(
+        frame,
+        columns[1],
+        &region_names(),
+        picker.selected_region,
+        picker.regions_focused,
+        "regions",
+    );
+
+    let targets = Paragraph::new(target_names(&app.config.targets))
+        .block(Block::default().borders(Borders::ALL).title("fan-out targets"));
+    frame.render_widget(targets, chunks[1]);
+
+    status_bar::draw(app, frame, chunks[2]);
+
+    let controls = Paragraph::new(
+        "hl/Tab (switch list) | Enter (connect) | Space (toggle fan-out target) | Escape (go back)",
+    )
+    .block(Block::default().borders(Borders::NONE).title("controls"));
+    frame.render_widget(controls, chunks[3]);
+}
+
+pub(crate) fn handle_input(
+    mut app: std::sync::MutexGuard<crate::App>,
+    key_code: KeyCode,
+    cwl: &Sender<AwsReq>,
+) {
+    let (len, selected) = if app.profiles.regions_focused {
+        (REGIONS.len() + 1, app.profiles.selected_region)
+    } else {
+        (app.profiles.profiles.len(), app.profiles.selected_profile)
+    };
+    let selected = match key_code {
+        KeyCode::Esc => {
+            app.selected = SelectedView::Overview;
+            return;
+        }
+        KeyCode::Tab | KeyCode::Char('h') | KeyCode::Char('l') | KeyCode::Left | KeyCode::Right => {
+            app.profiles.regions_focused = !app.profiles.regions_focused;
+            return;
+        }
+        KeyCode::Char('j') | KeyCode::Down => {
+            if len > 0 {
+                (selected + 1) % len
+            } else {
+                0
+            }
+        }
+        KeyCode::Char('k') | KeyCode::Up => {
+            if selected > 0 && len > 0 {
+                (selected - 1) % len
+            } else if selected == 0 && len > 0 {
+                len - 1
+            } else {
+                0
+            }
+        }
+        KeyCode::Enter => {
+            connect(app, cwl);
+            return;
+        }
+        KeyCode::Char(' ') => {
+            toggle_target(app, cwl);
+            return;
+        }
+        _ => return,
+    };
+    if app.profiles.regions_focused {
+        app.profiles.selected_region = selected;
+    } else {
+        app.profiles.selected_profile = selected;
+    }
+}
+
+fn connect(mut app: std::sync::MutexGuard<crate::App>, cwl: &Sender<AwsReq>) {
+    let picker = &app.profiles;
+    let profile = picker.profiles.get(picker.selected_profile).cloned();
+    let region = picker.selected_region.checked_sub(1).map(|i| REGIONS[i].to_string());
+    if profile.is_some() {
+        app.config.profile = profile;
+    }
+    app.config.region = region;
+    app.selected = SelectedView::Overview;
+    app.focused = Widget::LogGroups;
+    cwl.send(AwsReq::Connect).unwrap();
+}
+
+/// Adds the selected profile and region to the targets queries are fanned out to, or removes
+/// it when it's already there.
+fn toggle_target(mut app: std::sync::MutexGuard<crate::App>, cwl: &Sender<AwsReq>) {
+    let picker = &app.profiles;
+    let region = match picker.selected_region.checked_sub(1) {
+        Some(i) => REGIONS[i].to_string(),
+        None => {
+            app.status_message = StatusMessage::error("Select a region for the target");
+            return;
+        }
+    };
+    let target =
+        QueryTarget { region, profile: picker.profiles.get(picker.selected_profile).cloned() };
+    let targets = &mut app.config.targets;
+    match targets.iter().position(|t| *t == target) {
+        Some(i) => {
+            targets.remove(i);
+        }
+        None => targets.push(target),
+    }
+    cwl.send(AwsReq::Connect).unwrap();
+}
+
+/// Targets as `region/profile`, the profile is left out when it isn't set.
+pub(crate) fn target_names(targets: &[QueryTarget]) -> String {
+    targets
+        .iter()
+        .map(|t| match &t.profile {
+            Some(profile) => format!("{}/{}", t.region, profile),
+            None => t.region.clone(),
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(dir: &Path, name: &str, content: &str) -> PathBuf {
+        let path = dir.join(name);
+        fs::write(&path, content).unwrap();
+        path
+    }
+
+    #[test]
+    fn profiles_of_both_files_are_merged() {
+        let dir = std::env::temp_dir().join(format!("rcwi-profiles-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let config = write(
+            &dir,
+            "config",
+            "# [profile commented]\n\
+             [default]\nregion = eu-west-1\n\n\
+             [profile prod] ; production\nregion = us-east-1\n\
+             [ profile  staging ]\n\
+             [sso-session corp]\nsso_region = us-east-1\n\
+             [services local]\n",
+        );
+        let credentials = write(
+            &dir,
+            "credentials",
+            "[prod]\naws_access_key_id = x\n\n; [ignored]\n[default]\n[ci]\n",
+        );
+
+        let profiles = profiles_from(Some(&config), Some(&credentials)).unwrap();
+
+        assert_eq!(profiles, vec!["default", "ci", "prod", "staging"]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn missing_files_have_no_profiles() {
+        let missing = std::env::temp_dir().join("rcwi-profiles-missing").join("config");
+
+        assert!(profiles_from(Some(&missing), Some(&missing)).unwrap().is_empty());
+        assert!(profiles_from(None, None).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,430 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use tui::{
+    backend::CrosstermBackend,
+    layout::Rect,
+    style::{Color, Style},
+    widgets::{Block, Borders, Clear, List, ListItem, Paragraph},
+    Frame,
+};
+use unicode_width::UnicodeWidthStr;
+
+use crate::{
+    query_syntax::{self, FUNCTIONS},
+    Mode,
+};
+
+/// Older edits are forgotten once this many can be undone.
+const MAX_UNDO: usize = 100;
+const MAX_COMPLETIONS: usize = 10;
+const TAB: &str = "    ";
+
+/// Multi-line editor for the query, an alternative to editing it with `$EDITOR`.
+#[derive(Default)]
+pub(crate) struct QueryEditor {
+    pub(crate) active: bool,
+    lines: Vec<String>,
+    row: usize,
+    /// Cursor position in characters, not bytes.
+    col: usize,
+    undo: Vec<(Vec<String>, usize, usize)>,
+    /// Consecutive typed characters are undone together.
+    typing: bool,
+    /// Set while a word is being typed, completions are offered for it.
+    completing: bool,
+    completion: usize,
+}
+
+impl QueryEditor {
+    fn open(&mut self, query: &str) {
+        self.lines = query.split('\n').map(String::from).collect();
+        self.row = self.lines.len() - 1;
+        self.col = self.lines[self.row].chars().count();
+        self.undo = vec![];
+        self.typing = false;
+        self.completing = false;
+        self.active = true;
+    }
+
+    pub(crate) fn text(&self) -> String {
+        self.lines.join("\n")
+    }
+
+    fn byte_index(&self) -> usize {
+        let line = &self.lines[self.row];
+        line.char_indices().nth(self.col).map(|(i, _)| i).unwrap_or(line.len())
+    }
+
+    fn line_len(&self, row: usize) -> usize {
+        self.lines[row].chars().count()
+    }
+
+    fn snapshot(&mut self, typing: bool) {
+        if typing && self.typing {
+            return;
+        }
+        self.typing = typing;
+        self.undo.push((self.lines.clone(), self.row, self.col));
+        if self.undo.len() > MAX_UNDO {
+            self.undo.remove(0);
+        }
+    }
+
+    fn insert(&mut self, text: &str) {
+        let i = self.byte_index();
+        self.lines[self.row].insert_str(i, text);
+        self.col += text.chars().count();
+    }
+
+    fn newline(&mut self) {
+        let i = self.byte_index();
+        let rest = self.lines[self.row].split_off(i);
+        self.lines.insert(self.row + 1, rest);
+        self.row += 1;
+        self.col = 0;
+    }
+
+    fn backspace(&mut self) {
+        if self.col > 0 {
+            self.col -= 1;
+            let i = self.byte_index();
+            self.lines[self.row].remove(i);
+        } else if self.row > 0 {
+            let line = self.lines.remove(self.row);
+            self.row -= 1;
+            self.col = self.line_len(self.row);
+            self.lines[self.row].push_str(&line);
+        }
+    }
+
+    fn delete(&mut self) {
+        if self.col < self.line_len(self.row) {
+            let i = self.byte_index();
+            self.lines[self.row].remove(i);
+        } else if self.row + 1 < self.lines.len() {
+            let line = self.lines.remove(self.row + 1);
+            self.lines[self.row].push_str(&line);
+        }
+    }
+
+    fn move_cursor(&mut self, key_code: KeyCode) {
+        match key_code {
+            KeyCode::Left if self.col > 0 => self.col -= 1,
+            KeyCode::Left if self.row > 0 => {
+                self.row -= 1;
+                self.col = self.line_len(self.row);
+            }
+            KeyCode::Right if self.col < self.line_len(self.row) => self.col += 1,
+            KeyCode::Right if self.row + 1 < self.lines.len() => {
+                self.row += 1;
+                self.col = 0;
+            }
+            KeyCode::Up if self.row > 0 => {
+                self.row -= 1;
+                self.col = self.col.min(self.line_len(self.row));
+            }
+            KeyCode::Down if self.row + 1 < self.lines.len() => {
+                self.row += 1;
+                self.col = self.col.min(self.line_len(self.row));
+            }
+            KeyCode::Home => self.col = 0,
+            KeyCode::End => self.col = self.line_len(self.row),
+            _ => {}
+        }
+    }
+
+    /// The word, or `@`-field, right before the cursor.
+    fn word_before_cursor(&self) -> String {
+        let before: Vec<char> = self.lines[self.row].chars().take(self.col).collect();
+        let start = before
+            .iter()
+            .rposition(|c| !query_syntax::is_word(*c) && *c != '@')
+            .map(|i| i + 1)
+            .unwrap_or(0);
+        before[start..].iter().collect()
+    }
+
+    /// Fields and functions starting with the word being typed, functions come with an opening
+    /// parenthesis.
+    pub(crate) fn completions<'a>(&self, fields: impl Iterator<Item = &'a str>) -> Vec<String> {
+        if !self.completing {
+            return vec![];
+        }
+        let word = self.word_before_cursor();
+        if word.is_empty() {
+            return vec![];
+        }
+        let prefix = word.to_lowercase();
+        fields
+            .map(String::from)
+            .chain(FUNCTIONS.iter().map(|f| format!("{}(", f)))
+            .filter(|c| *c != word && c.to_lowercase().starts_with(&prefix))
+            .take(MAX_COMPLETIONS)
+            .collect()
+    }
+
+    /// Replaces the word before the cursor with `completion`.
+    fn complete(&mut self, completion: &str) {
+        let word_len = self.word_before_cursor().chars().count();
+        self.snapshot(false);
+        for _ in 0..word_len {
+            self.backspace();
+        }
+        self.insert(completion);
+        self.completing = false;
+    }
+
+    pub(crate) fn undo(&mut self) {
+        if let Some((lines, row, col)) = self.undo.pop() {
+            self.lines = lines;
+            self.row = row;
+            self.col = col;
+        }
+        self.typing = false;
+    }
+}
+
+/// Opens the editor on the current query.
+pub(crate) fn open(app: &mut std::sync::MutexGuard<crate::App>) {
+    let query = app.query.clone();
+    app.query_editor.open(&query);
+    app.mode = Mode::Insert;
+}
+
+/// Draws the query with syntax highlighting, with a cursor while the editor is open.
+pub(crate) fn draw(
+    app: &std::sync::MutexGuard<crate::App>,
+    frame: &mut Frame<CrosstermBackend<std::io::Stdout>>,
+    area: Rect,
+    style: Style,
+) {
+    let editor = &app.query_editor;
+    let title = if editor.active { "query (Escape to stop editing)" } else { "query" };
+    let height = area.height.saturating_sub(2) as usize;
+    let scroll =
+        if editor.active { editor.row.saturating_sub(height.saturating_sub(1)) } else { 0 };
+    let query = if editor.active { editor.text() } else { app.query.clone() };
+    let paragraph = Paragraph::new(query_syntax::highlight(&query))
+        .style(style)
+        .scroll((scroll as u16, 0))
+        .block(Block::default().borders(Borders::ALL).title(title));
+    frame.render_widget(paragraph, area);
+    if !editor.active {
+        return;
+    }
+    let line = &editor.lines[editor.row];
+    let before: String = line.chars().take(editor.col).collect();
+    let (x, y) = (area.x + before.width() as u16 + 1, area.y + (editor.row - scroll) as u16 + 1);
+    frame.set_cursor(x, y);
+
+    let completions = editor.completions(app.fields.names());
+    if completions.is_empty() {
+        return;
+    }
+    let size = frame.size();
+    let width =
+        (completions.iter().map(|c| c.width()).max().unwrap_or(0) as u16 + 2).min(size.width);
+    let height = (completions.len() as u16 + 2).min(size.height);
+    let popup = Rect::new(
+        x.min(size.width - width),
+        if y + 1 + height <= size.height { y + 1 } else { y.saturating_sub(height) },
+        width,
+        height,
+    );
+    let selected = editor.completion % completions.len();
+    let items: Vec<ListItem> = completions
+        .iter()
+        .enumerate()
+        .map(|(i, c)| {
+            ListItem::new(c.as_str()).style(if i == selected {
+                Style::default().fg(Color::Red)
+            } else {
+                Style::default()
+            })
+        })
+        .collect();
+    frame.render_widget(Clear, popup);
+    frame.render_widget(List::new(items).block(Block::default().borders(Borders::ALL)), popup);
+}
+
+/// Edits the query, Ctrl-z undoes the last edit and other chords with Ctrl or Alt are ignored.
+pub(crate) fn handle_input(mut app: std::sync::MutexGuard<crate::App>, key: KeyEvent) {
+    if key.modifiers.intersects(KeyModifiers::CONTROL | KeyModifiers::ALT) {
+        if key.code == KeyCode::Char('z') && key.modifiers == KeyModifiers::CONTROL {
+            app.query_editor.undo();
+            app.query = app.query_editor.text();
+        }
+        return;
+    }
+    let key_code = key.code;
+    let completions = app.query_editor.completions(app.fields.names());
+    let editor = &mut app.query_editor;
+    if !completions.is_empty() {
+        let selected = editor.completion % completions.len();
+        match key_code {
+            KeyCode::Tab => {
+                editor.complete(&completions[selected]);
+                app.query = app.query_editor.text();
+                return;
+            }
+            KeyCode::Down => {
+                editor.completion = (selected + 1) % completions.len();
+                return;
+            }
+            KeyCode::Up => {
+                editor.completion = (selected + completions.len() - 1) % completions.len();
+                return;
+            }
+            KeyCode::Esc => {
+                editor.completing = false;
+                return;
+            }
+            _ => {}
+        }
+    }
+    editor.completing = false;
+    match key_code {
+        KeyCode::Esc => {
+            editor.active = false;
+            app.mode = Mode::Normal;
+            return;
+        }
+        KeyCode::Char(c) => {
+            editor.snapshot(!c.is_whitespace());
+            editor.insert(&c.to_string());
+            editor.completing = true;
+            editor.completion = 0;
+        }
+        KeyCode::Tab => {
+            editor.snapshot(false);
+            editor.insert(TAB);
+        }
+        KeyCode::Enter => {
+            editor.snapshot(false);
+            editor.newline();
+        }
+        KeyCode::Backspace => {
+            editor.snapshot(false);
+            editor.backspace();
+            editor.completing = true;
+            editor.completion = 0;
+        }
+        KeyCode::Delete => {
+            editor.snapshot(false);
+            editor.delete();
+        }
+        k => {
+            editor.typing = false;
+            editor.move_cursor(k);
+            return;
+        }
+    }
+    app.query = app.query_editor.text();
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+    use crate::App;
+
+    fn editor_on(query: &str) -> Mutex<App> {
+        let app = Mutex::new(App { query: query.to_string(), ..App::default() });
+        open(&mut app.lock().unwrap());
+        app
+    }
+
+    fn press(app: &Mutex<App>, code: KeyCode, modifiers: KeyModifiers) {
+        handle_input(app.lock().unwrap(), KeyEvent::new(code, modifiers));
+    }
+
+    fn keys(app: &Mutex<App>, codes: &[KeyCode]) {
+        codes.iter().for_each(|code| press(app, *code, KeyModifiers::NONE));
+    }
+
+    fn typed(app: &Mutex<App>, text: &str) {
+        text.chars().for_each(|c| press(app, KeyCode::Char(c), KeyModifiers::NONE));
+    }
+
+    fn query(app: &Mutex<App>) -> String {
+        app.lock().unwrap().query.clone()
+    }
+
+    #[test]
+    fn typed_words_are_undone_together() {
+        let app = editor_on("fields");
+        typed(&app, " @message");
+        keys(&app, &[KeyCode::Enter]);
+        typed(&app, "| limit");
+        assert_eq!(query(&app), "fields @message\n| limit");
+
+        press(&app, KeyCode::Char('z'), KeyModifiers::CONTROL);
+        assert_eq!(query(&app), "fields @message\n| ");
+        press(&app, KeyCode::Char('z'), KeyModifiers::CONTROL);
+        press(&app, KeyCode::Char('z'), KeyModifiers::CONTROL);
+        assert_eq!(query(&app), "fields @message\n");
+        press(&app, KeyCode::Char('z'), KeyModifiers::CONTROL);
+        press(&app, KeyCode::Char('z'), KeyModifiers::CONTROL);
+        assert_eq!(query(&app), "fields ");
+    }
+
+    #[test]
+    fn backspace_and_delete_join_lines() {
+        let app = editor_on("fields @message\n| limit 5");
+        keys(&app, &[KeyCode::Home, KeyCode::Backspace]);
+        assert_eq!(query(&app), "fields @message| limit 5");
+
+        keys(&app, &[KeyCode::Enter, KeyCode::Up, KeyCode::End, KeyCode::Delete]);
+        assert_eq!(query(&app), "fields @message| limit 5");
+        keys(&app, &[KeyCode::Delete]);
+        assert_eq!(query(&app), "fields @message limit 5");
+
+        let app = editor_on("a\nb");
+        keys(&app, &[KeyCode::Down, KeyCode::End, KeyCode::Delete]);
+        assert_eq!(query(&app), "a\nb");
+        keys(&app, &[KeyCode::Up, KeyCode::Home, KeyCode::Backspace]);
+        assert_eq!(query(&app), "a\nb");
+    }
+
+    #[test]
+    fn cursor_moves_by_characters() {
+        let app = editor_on("é漢\nü");
+        keys(&app, &[KeyCode::Up, KeyCode::End, KeyCode::Left]);
+        typed(&app, "x");
+        assert_eq!(query(&app), "éx漢\nü");
+
+        keys(&app, &[KeyCode::Right, KeyCode::Right]);
+        typed(&app, "y");
+        assert_eq!(query(&app), "éx漢\nyü");
+
+        keys(&app, &[KeyCode::Left, KeyCode::Left, KeyCode::Backspace]);
+        assert_eq!(query(&app), "éx\nyü");
+    }
+
+    #[test]
+    fn completions_replace_the_word_being_typed() {
+        let app = editor_on("stats ");
+        typed(&app, "COU");
+        let completions = app.lock().unwrap().query_editor.completions(std::iter::empty());
+        assert_eq!(completions, vec!["count(", "count_distinct("]);
+
+        keys(&app, &[KeyCode::Down, KeyCode::Tab]);
+        assert_eq!(query(&app), "stats count_distinct(");
+
+        typed(&app, "@");
+        keys(&app, &[KeyCode::Esc, KeyCode::Tab]);
+        assert_eq!(query(&app), "stats count_distinct(@    ");
+    }
+
+    #[test]
+    fn chords_are_not_typed() {
+        let app = editor_on("");
+        press(&app, KeyCode::Char('a'), KeyModifiers::CONTROL);
+        press(&app, KeyCode::Char('x'), KeyModifiers::ALT);
+        press(&app, KeyCode::Char('z'), KeyModifiers::CONTROL | KeyModifiers::ALT);
+        press(&app, KeyCode::Char('F'), KeyModifiers::SHIFT);
+
+        assert_eq!(query(&app), "F");
+        assert!(app.lock().unwrap().query_editor.active);
+    }
+}
@@ -0,0 +1,288 @@
+use std::{error::Error, fmt::Display};
+
+use crate::query_syntax::{tokenize, Token, TokenKind, COMMANDS};
+
+/// Commands that work without arguments.
+const STANDALONE_COMMANDS: &[&str] = &["diff", "anomaly"];
+
+/// A problem that would make `StartQuery` reject the query, positions start at 1.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct LintError {
+    pub(crate) line: usize,
+    pub(crate) column: usize,
+    pub(crate) message: String,
+}
+
+impl Display for LintError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "line {}, column {}: {}", self.line, self.column, self.message)
+    }
+}
+
+impl Error for LintError {}
+
+struct Located<'a> {
+    token: Token<'a>,
+    line: usize,
+    column: usize,
+}
+
+impl Located<'_> {
+    fn error(&self, message: String) -> LintError {
+        LintError { line: self.line, column: self.column, message }
+    }
+
+    fn is(&self, text: &str) -> bool {
+        self.token.text.eq_ignore_ascii_case(text)
+    }
+}
+
+/// Checks the structure of `query` without sending it anywhere.
+///
+/// Returns warnings about queries that are valid but won't work well with the results view,
+/// like results missing `@timestamp` or `@message`, and about commands that aren't known here.
+/// Those are left for `StartQuery` to check, Insights keeps adding commands.
+pub(crate) fn lint(query: &str) -> Result<Vec<String>, LintError> {
+    let tokens = significant_tokens(query)?;
+    check_parentheses(&tokens)?;
+    let commands = split_commands(&tokens)?;
+    let mut warnings = vec![];
+    for (command, args) in &commands {
+        if COMMANDS.contains(&command.token.text.to_lowercase().as_str()) {
+            check_command(command, args)?;
+        } else {
+            warnings.push(
+                command.error(format!("unknown command `{}`", command.token.text)).to_string(),
+            );
+        }
+    }
+    warnings.extend(projection_warnings(&commands));
+    Ok(warnings)
+}
+
+/// Tokens of all lines without whitespace and comments, failing on unclosed strings.
+fn significant_tokens(query: &str) -> Result<Vec<Located<'_>>, LintError> {
+    let mut tokens = vec![];
+    for (i, line) in query.split('\n').enumerate() {
+        for token in tokenize(line) {
+            let located =
+                Located { token, line: i + 1, column: line[..token.start].chars().count() + 1 };
+            match token.kind {
+                TokenKind::Whitespace | TokenKind::Comment => continue,
+                TokenKind::String | TokenKind::Regex | TokenKind::Field
+                    if !is_closed(token.text) =>
+                {
+                    let what = match token.kind {
+                        TokenKind::String => "string",
+                        TokenKind::Regex => "regex",
+                        _ => "field name",
+                    };
+                    return Err(located.error(format!("Unclosed {}", what)));
+                }
+                _ => tokens.push(located),
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+/// Whether a quoted token ends with an unescaped quote, tokens that aren't quoted always are.
+fn is_closed(text: &str) -> bool {
+    let quote = match text.chars().next() {
+        Some(c @ ('"' | '\'' | '`' | '/')) => c,
+        _ => return true,
+    };
+    let rest = &text[1..];
+    match rest.strip_suffix(quote) {
+        Some(inner) => inner.chars().rev().take_while(|c| *c == '\\').count() % 2 == 0,
+        None => false,
+    }
+}
+
+fn check_parentheses(tokens: &[Located]) -> Result<(), LintError> {
+    let mut open = vec![];
+    for token in tokens.iter().filter(|t| t.token.kind == TokenKind::Other) {
+        match token.token.text {
+            "(" => open.push(token),
+            ")" if open.pop().is_none() => return Err(token.error("Unmatched `)`".into())),
+            _ => {}
+        }
+    }
+    match open.pop() {
+        Some(token) => Err(token.error("Unclosed `(`".into())),
+        None => Ok(()),
+    }
+}
+
+/// Splits the query at pipes into commands and their arguments.
+fn split_commands<'a, 'b>(
+    tokens: &'b [Located<'a>],
+) -> Result<Vec<(&'b Located<'a>, &'b [Located<'a>])>, LintError> {
+    if tokens.is_empty() {
+        return Err(LintError { line: 1, column: 1, message: "The query is empty".into() });
+    }
+    let mut commands = vec![];
+    let mut start = 0;
+    for end in (0..=tokens.len()).filter(|i| match tokens.get(*i) {
+        Some(t) => t.token.kind == TokenKind::Pipe,
+        None => true,
+    }) {
+        let segment = &tokens[start..end];
+        let command = match segment.first() {
+            Some(command) => command,
+            None if end < tokens.len() => {
+                return Err(tokens[end].error("Expected a command before `|`".into()))
+            }
+            None => return Err(tokens[end - 1].error("Expected a command after `|`".into())),
+        };
+        if !matches!(command.token.kind, TokenKind::Word | TokenKind::Command) {
+            return Err(
+                command.error(format!("Expected a command, found `{}`", command.token.text))
+            );
+        }
+        commands.push((command, &segment[1..]));
+        start = end + 1;
+    }
+    Ok(commands)
+}
+
+fn check_command(command: &Located, args: &[Located]) -> Result<(), LintError> {
+    let name = command.token.text.to_lowercase();
+    if args.is_empty() && !STANDALONE_COMMANDS.contains(&name.as_str()) {
+        return Err(command.error(format!("Expected arguments after `{}`", name)));
+    }
+    for (i, arg) in args.iter().enumerate() {
+        if arg.is(",") && (i == 0 || i + 1 == args.len() || args[i + 1].is(",")) {
+            return Err(arg.error("Unexpected `,`".into()));
+        }
+    }
+    match name.as_str() {
+        "limit" => match args {
+            [count] if count.token.text.parse::<u32>().is_ok() => Ok(()),
+            _ => Err(args[0].error("`limit` takes a single number".into())),
+        },
+        "stats" => {
+            let mut depth = 0;
+            let mut by = None;
+            for (i, arg) in args.iter().enumerate() {
+                match arg.token.text {
+                    "(" => depth += 1,
+                    ")" => depth -= 1,
+                    _ if depth == 0 && arg.is("by") => {
+                        by = Some(i);
+                        break;
+                    }
+                    _ => {}
+                }
+            }
+            let aggregations = &args[..by.unwrap_or(args.len())];
+            if !aggregations.iter().any(|a| a.is("(")) {
+                return Err(
+                    command.error("Expected an aggregation like count(*) after `stats`".into())
+                );
+            }
+            match by {
+                Some(i) if i + 1 == args.len() => {
+                    Err(args[i].error("Expected fields to group by after `by`".into()))
+                }
+                _ => Ok(()),
+            }
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Warns when the last command choosing the fields of the results leaves out `@timestamp` or
+/// `@message`, queries ending with `stats` aren't expected to have them.
+fn projection_warnings(commands: &[(&Located, &[Located])]) -> Vec<String> {
+    let projection = commands
+        .iter()
+        .rev()
+        .find(|(command, _)| command.is("fields") || command.is("display") || command.is("stats"));
+    let args = match projection {
+        Some((command, args)) if !command.is("stats") => args,
+        _ => return vec![],
+    };
+    let mut warnings = vec![];
+    if !args.iter().any(|a| a.is("@timestamp")) {
+        warnings.push("results have no @timestamp, merged results aren't sorted by time".into());
+    }
+    if !args.iter().any(|a| a.is("@message")) {
+        warnings.push("results have no @message, y copies all columns".into());
+    }
+    warnings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error(query: &str) -> (usize, usize, String) {
+        let err = lint(query).unwrap_err();
+        (err.line, err.column, err.message)
+    }
+
+    #[test]
+    fn valid_queries_pass() {
+        for query in [
+            "fields @timestamp, @message | sort @timestamp desc | limit 20",
+            "filter @message like /a|b/ and msg = \"x | y\"",
+            "stats count(*) as n, avg(d) by bin(5m), host",
+            "stats count_distinct(user)",
+            "parse @message 'user=*, ' as user\n# comment | not a command\n| display user",
+            "fields `odd field` | filter `odd field` = 'it\\'s'",
+            "pattern @message | diff",
+        ] {
+            assert!(lint(query).is_ok(), "{}", query);
+        }
+    }
+
+    #[test]
+    fn errors_are_reported_with_line_and_column() {
+        let cases = [
+            ("filter @message like \"error", (1, 22, "Unclosed string")),
+            ("fields @message\n| filter msg = 'x", (2, 16, "Unclosed string")),
+            ("filter @message like /error", (1, 22, "Unclosed regex")),
+            ("fields `odd", (1, 8, "Unclosed field name")),
+            ("stats count(* by host", (1, 12, "Unclosed `(`")),
+            ("stats count(*)) by host", (1, 15, "Unmatched `)`")),
+            ("", (1, 1, "The query is empty")),
+            ("fields @message || limit 5", (1, 18, "Expected a command before `|`")),
+            ("fields @message |", (1, 17, "Expected a command after `|`")),
+            ("| fields @message", (1, 1, "Expected a command before `|`")),
+            ("fields @message | @ptr", (1, 19, "Expected a command, found `@ptr`")),
+            ("fields\n| limit 5", (1, 1, "Expected arguments after `fields`")),
+            ("fields a,, b", (1, 9, "Unexpected `,`")),
+            ("fields a, b,", (1, 12, "Unexpected `,`")),
+            ("limit ten", (1, 7, "`limit` takes a single number")),
+            ("limit 5 10", (1, 7, "`limit` takes a single number")),
+            ("limit -5", (1, 7, "`limit` takes a single number")),
+            (
+                "stats host by bin(1m)",
+                (1, 1, "Expected an aggregation like count(*) after `stats`"),
+            ),
+            ("stats count(*) by", (1, 16, "Expected fields to group by after `by`")),
+        ];
+        for (query, (line, column, message)) in cases {
+            assert_eq!(error(query), (line, column, message.to_string()), "{}", query);
+        }
+    }
+
+    #[test]
+    fn unknown_commands_are_warnings() {
+        let warnings = lint("fields @timestamp, @message\n  | frobnicate @message").unwrap();
+        assert_eq!(warnings, vec!["line 2, column 5: unknown command `frobnicate`"]);
+    }
+
+    #[test]
+    fn missing_timestamp_and_message_are_warnings() {
+        assert_eq!(
+            lint("fields @timestamp, @message | fields host").unwrap(),
+            vec![
+                "results have no @timestamp, merged results aren't sorted by time",
+                "results have no @message, y copies all columns",
+            ]
+        );
+        assert!(lint("fields host | stats count(*) by host").unwrap().is_empty());
+    }
+}
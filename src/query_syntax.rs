@@ -0,0 +1,318 @@
+use tui::{
+    style::{Color, Modifier, Style},
+    text::{Span, Spans, Text},
+};
+
+/// Commands of the Insights query language, lowercase.
+pub(crate) const COMMANDS: &[&str] = &[
+    "fields",
+    "filter",
+    "stats",
+    "sort",
+    "limit",
+    "parse",
+    "display",
+    "dedup",
+    "unmask",
+    "pattern",
+    "diff",
+    "anomaly",
+    "unnest",
+    "lookup",
+    "filterindex",
+];
+
+/// Functions offered as completions, see the Insights query syntax documentation.
+pub(crate) const FUNCTIONS: &[&str] = &[
+    "abs",
+    "avg",
+    "bin",
+    "ceil",
+    "coalesce",
+    "concat",
+    "count",
+    "count_distinct",
+    "datefloor",
+    "dateceil",
+    "earliest",
+    "floor",
+    "fromMillis",
+    "greatest",
+    "isblank",
+    "isempty",
+    "isIpInSubnet",
+    "ispresent",
+    "isValidIp",
+    "latest",
+    "least",
+    "ltrim",
+    "max",
+    "min",
+    "pct",
+    "replace",
+    "rtrim",
+    "sortsFirst",
+    "sortsLast",
+    "sqrt",
+    "stddev",
+    "strcontains",
+    "strlen",
+    "substr",
+    "sum",
+    "toMillis",
+    "tolower",
+    "toupper",
+    "trim",
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum TokenKind {
+    Command,
+    Pipe,
+    String,
+    Regex,
+    /// `@`-fields and fields quoted with backticks.
+    Field,
+    Comment,
+    Word,
+    Whitespace,
+    Other,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct Token<'a> {
+    pub(crate) kind: TokenKind,
+    pub(crate) text: &'a str,
+    /// Byte offset in the line.
+    pub(crate) start: usize,
+}
+
+/// Splits one line of a query into tokens, concatenating their text gives back the line.
+///
+/// Strings and regexes are cut off at the end of the line when they aren't closed.
+pub(crate) fn tokenize(line: &str) -> Vec<Token<'_>> {
+    let mut tokens: Vec<Token> = vec![];
+    let mut chars = line.char_indices().peekable();
+    while let Some((start, c)) = chars.next() {
+        let regex_allowed = regex_allowed(&tokens);
+        let kind = match c {
+            '#' => {
+                while chars.next().is_some() {}
+                TokenKind::Comment
+            }
+            '|' => TokenKind::Pipe,
+            '"' | '\'' | '`' | '/' if c != '/' || regex_allowed => {
+                let mut escaped = false;
+                for (_, next) in chars.by_ref() {
+                    if next == c && !escaped {
+                        break;
+                    }
+                    escaped = next == '\\' && !escaped;
+                }
+                match c {
+                    '/' => TokenKind::Regex,
+                    '`' => TokenKind::Field,
+                    _ => TokenKind::String,
+                }
+            }
+            '@' => {
+                while chars.next_if(|(_, c)| is_word(*c)).is_some() {}
+                TokenKind::Field
+            }
+            c if is_word(c) => {
+                while chars.next_if(|(_, c)| is_word(*c)).is_some() {}
+                TokenKind::Word
+            }
+            c if c.is_whitespace() => {
+                while chars.next_if(|(_, c)| c.is_whitespace()).is_some() {}
+                TokenKind::Whitespace
+            }
+            _ => TokenKind::Other,
+        };
+        let end = chars.peek().map(|(i, _)| *i).unwrap_or(line.len());
+        let text = &line[start..end];
+        let kind = match kind {
+            TokenKind::Word if is_command(&tokens, text) => TokenKind::Command,
+            kind => kind,
+        };
+        tokens.push(Token { kind, text, start });
+    }
+    tokens
+}
+
+/// The commands of a query without comments, one for every part between pipes.
+pub(crate) fn commands(query: &str) -> Vec<String> {
+    let mut commands = vec![String::new()];
+    for line in query.split('\n') {
+        for token in tokenize(line) {
+            match token.kind {
+                TokenKind::Pipe => commands.push(String::new()),
+                TokenKind::Comment => {}
+                _ => commands.last_mut().unwrap().push_str(token.text),
+            }
+        }
+        commands.last_mut().unwrap().push(' ');
+    }
+    commands.iter().map(|c| c.trim().to_string()).filter(|c| !c.is_empty()).collect()
+}
+
+/// Whether `command`, as returned by [`commands`], is the command `name`.
+pub(crate) fn command_is(command: &str, name: &str) -> bool {
+    command.split_whitespace().next().map(|c| c.eq_ignore_ascii_case(name)).unwrap_or(false)
+}
+
+pub(crate) fn is_word(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '.'
+}
+
+fn last_significant<'a>(tokens: &[Token<'a>]) -> Option<Token<'a>> {
+    tokens.iter().rev().find(|t| t.kind != TokenKind::Whitespace).copied()
+}
+
+/// A command starts the query or follows a pipe, `fields` in `stats count() by fields` isn't one.
+fn is_command(tokens: &[Token], word: &str) -> bool {
+    let previous = last_significant(tokens);
+    COMMANDS.contains(&word.to_lowercase().as_str())
+        && matches!(previous, None | Some(Token { kind: TokenKind::Pipe, .. }))
+}
+
+/// A `/` starts a regex after an operator, `like` or the field given to `parse`, anywhere else
+/// it's a division.
+fn regex_allowed(tokens: &[Token]) -> bool {
+    let in_parse = tokens
+        .iter()
+        .rev()
+        .find(|t| t.kind == TokenKind::Command)
+        .map(|t| t.text.eq_ignore_ascii_case("parse"))
+        .unwrap_or(false);
+    match last_significant(tokens) {
+        None => false,
+        Some(token) => match token.kind {
+            TokenKind::Command | TokenKind::Pipe => true,
+            TokenKind::Field => in_parse,
+            TokenKind::Word => {
+                ["like", "in", "and", "or", "not"].contains(&token.text.to_lowercase().as_str())
+            }
+            TokenKind::Other => token.text.ends_with(['=', '~', '(', ',', '!']),
+            _ => false,
+        },
+    }
+}
+
+pub(crate) fn style(kind: TokenKind) -> Style {
+    match kind {
+        TokenKind::Command => Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD),
+        TokenKind::Pipe => Style::default().fg(Color::Magenta),
+        TokenKind::String => Style::default().fg(Color::Green),
+        TokenKind::Regex => Style::default().fg(Color::LightRed),
+        TokenKind::Field => Style::default().fg(Color::LightBlue),
+        TokenKind::Comment => Style::default().fg(Color::DarkGray),
+        TokenKind::Word | TokenKind::Whitespace | TokenKind::Other => Style::default(),
+    }
+}
+
+/// The query as styled text, one line per query line.
+pub(crate) fn highlight(query: &str) -> Text<'_> {
+    Text::from(
+        query
+            .split('\n')
+            .map(|line| {
+                Spans::from(
+                    tokenize(line)
+                        .into_iter()
+                        .map(|t| Span::styled(t.text, style(t.kind)))
+                        .collect::<Vec<_>>(),
+                )
+            })
+            .collect::<Vec<_>>(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kinds(line: &str) -> Vec<(TokenKind, &str)> {
+        tokenize(line)
+            .into_iter()
+            .filter(|t| t.kind != TokenKind::Whitespace)
+            .map(|t| (t.kind, t.text))
+            .collect()
+    }
+
+    #[test]
+    fn tokens_cover_the_whole_line() {
+        for line in [
+            "fields @timestamp, @message | filter @message like /a|b/ # done",
+            "parse @message \"user=*\" as user | stats count(*) by bin(5m)",
+            "filter msg = 'it\\'s' and `odd field` > 1 / 2",
+            "filter @message like \"unclosed",
+        ] {
+            let text: String = tokenize(line).iter().map(|t| t.text).collect();
+            assert_eq!(text, line);
+        }
+    }
+
+    #[test]
+    fn tokens_are_classified() {
+        let cases: Vec<(&str, Vec<(TokenKind, &str)>)> = vec![
+            (
+                "filter @message like /a|b/",
+                vec![
+                    (TokenKind::Command, "filter"),
+                    (TokenKind::Field, "@message"),
+                    (TokenKind::Word, "like"),
+                    (TokenKind::Regex, "/a|b/"),
+                ],
+            ),
+            (
+                "stats sum(x) / 2 by fields",
+                vec![
+                    (TokenKind::Command, "stats"),
+                    (TokenKind::Word, "sum"),
+                    (TokenKind::Other, "("),
+                    (TokenKind::Word, "x"),
+                    (TokenKind::Other, ")"),
+                    (TokenKind::Other, "/"),
+                    (TokenKind::Word, "2"),
+                    (TokenKind::Word, "by"),
+                    (TokenKind::Word, "fields"),
+                ],
+            ),
+            (
+                "display 'a \\' b' | `my field` # note",
+                vec![
+                    (TokenKind::Command, "display"),
+                    (TokenKind::String, "'a \\' b'"),
+                    (TokenKind::Pipe, "|"),
+                    (TokenKind::Field, "`my field`"),
+                    (TokenKind::Comment, "# note"),
+                ],
+            ),
+            (
+                "unmask @message",
+                vec![(TokenKind::Command, "unmask"), (TokenKind::Field, "@message")],
+            ),
+        ];
+        for (line, expected) in cases {
+            assert_eq!(kinds(line), expected, "{}", line);
+        }
+    }
+
+    #[test]
+    fn commands_are_split_at_pipes_without_comments() {
+        let query = "fields @timestamp # first\n| filter a = \"x|y\"\n\n|  sort @timestamp desc |";
+        assert_eq!(
+            commands(query),
+            vec!["fields @timestamp", "filter a = \"x|y\"", "sort @timestamp desc"]
+        );
+    }
+
+    #[test]
+    fn command_is_matches_the_first_word_ignoring_case() {
+        assert!(command_is("STATS count(*) by bin(1m)", "stats"));
+        assert!(!command_is("stats_x count(*)", "stats"));
+        assert!(!command_is("filter stats = 1", "stats"));
+        assert!(!command_is("", "stats"));
+    }
+}
@@ -0,0 +1,226 @@
+use std::collections::HashSet;
+
+use indicium::simple::{Indexable, SearchIndex};
+use regex::{Regex, RegexBuilder};
+
+use crate::overview::ResultRow;
+
+/// Search within the loaded results, started with `/` in the results.
+#[derive(Default)]
+pub(crate) struct ResultsSearch {
+    /// Search being typed in the prompt, `None` when the prompt is closed.
+    pub(crate) input: Option<String>,
+    /// The search `matches` are for, empty when there is none.
+    pub(crate) pattern: String,
+    /// Indices of the matching rows, in order.
+    pub(crate) matches: Vec<usize>,
+    /// The rows shown by [`key`], filled as rows arrive like the log groups are indexed.
+    index: SearchIndex<String>,
+}
+
+impl Indexable for ResultRow {
+    fn strings(&self) -> Vec<String> {
+        self.fields.iter().filter(|(f, _)| f != "@ptr").map(|(_, v)| v.clone()).collect()
+    }
+}
+
+impl ResultsSearch {
+    /// Finds the rows matching `pattern`, an empty pattern ends the search.
+    pub(crate) fn search(&mut self, pattern: &str, rows: &[ResultRow]) -> Result<(), String> {
+        self.matches = self.find_matches(pattern, rows, 0..rows.len())?;
+        self.pattern = pattern.to_string();
+        Ok(())
+    }
+
+    /// Indexes the rows again once they were replaced and finds the matches in them.
+    pub(crate) fn update(&mut self, rows: &[ResultRow]) {
+        self.index = SearchIndex::default();
+        for (i, row) in rows.iter().enumerate() {
+            self.index.insert(&key(i, row), row);
+        }
+        self.matches = self.find_matches(&self.pattern, rows, 0..rows.len()).unwrap_or_default();
+    }
+
+    /// Indexes the rows inserted at `added`, moves the matches to where their rows were `moved`
+    /// and adds the new rows that match.
+    pub(crate) fn rows_added(&mut self, moved: &[usize], added: &[usize], rows: &[ResultRow]) {
+        for i in added {
+            self.index.insert(&key(*i, &rows[*i]), &rows[*i]);
+        }
+        if self.pattern.is_empty() {
+            return;
+        }
+        if parse_regex(&self.pattern).is_some() {
+            let found =
+                self.find_matches(&self.pattern, rows, added.iter().copied()).unwrap_or_default();
+            self.matches = self.matches.iter().map(|i| moved[*i]).chain(found).collect();
+            self.matches.sort_unstable();
+        } else {
+            // The index caps the rows it finds, so the new rows can push out earlier matches.
+            self.matches =
+                self.find_matches(&self.pattern, rows, 0..rows.len()).unwrap_or_default();
+        }
+    }
+
+    /// The first match below `selected`, wrapping around to the top.
+    pub(crate) fn next(&self, selected: usize) -> Option<usize> {
+        self.matches
+            .iter()
+            .copied()
+            .find(|i| *i > selected)
+            .or_else(|| self.matches.first().copied())
+    }
+
+    /// The first match above `selected`, wrapping around to the bottom.
+    pub(crate) fn previous(&self, selected: usize) -> Option<usize> {
+        self.matches
+            .iter()
+            .rev()
+            .copied()
+            .find(|i| *i < selected)
+            .or_else(|| self.matches.last().copied())
+    }
+
+    /// A pattern like `/5\d\d/` is a case insensitive regex matched against every field of the
+    /// rows at `indices`. Any other pattern is looked up in the search index, by keyword like
+    /// the log groups are filtered.
+    fn find_matches(
+        &self,
+        pattern: &str,
+        rows: &[ResultRow],
+        indices: impl Iterator<Item = usize>,
+    ) -> Result<Vec<usize>, String> {
+        if pattern.is_empty() {
+            return Ok(vec![]);
+        }
+        if let Some(regex) = parse_regex(pattern) {
+            let regex = regex?;
+            return Ok(indices
+                .filter(|i| rows[*i].strings().iter().any(|v| regex.is_match(v)))
+                .collect());
+        }
+        let found: HashSet<&String> = self.index.search(pattern).into_iter().collect();
+        Ok(indices.filter(|i| found.contains(&key(*i, &rows[*i]))).collect())
+    }
+}
+
+/// Rows are indexed by `@ptr`, which stays the same as rows arrive above them. Rows without
+/// one, like those of `stats`, are only ever replaced all at once so their index is enough.
+fn key(index: usize, row: &ResultRow) -> String {
+    match row.get("@ptr") {
+        Some(ptr) => ptr.to_string(),
+        None => format!("#{}", index),
+    }
+}
+
+/// The regex between the slashes of a pattern like `/5\d\d/`, `None` for any other pattern.
+fn parse_regex(pattern: &str) -> Option<Result<Regex, String>> {
+    let regex =
+        pattern.strip_prefix('/').and_then(|p| p.strip_suffix('/')).filter(|p| !p.is_empty())?;
+    Some(
+        RegexBuilder::new(regex)
+            .case_insensitive(true)
+            .build()
+            .map_err(|err| format!("Invalid regex: {}", err)),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rows(messages: &[&str]) -> Vec<ResultRow> {
+        messages
+            .iter()
+            .enumerate()
+            .map(|(i, m)| ResultRow {
+                fields: vec![
+                    ("@ptr".to_string(), format!("ptr-{}", i)),
+                    ("@message".to_string(), m.to_string()),
+                ],
+            })
+            .collect()
+    }
+
+    fn indexed(rows: &[ResultRow]) -> ResultsSearch {
+        let mut search = ResultsSearch::default();
+        search.update(rows);
+        search
+    }
+
+    fn matches(pattern: &str, messages: &[&str]) -> Result<Vec<usize>, String> {
+        let rows = rows(messages);
+        let mut search = indexed(&rows);
+        search.search(pattern, &rows)?;
+        Ok(search.matches)
+    }
+
+    #[test]
+    fn text_is_looked_up_by_keyword_in_the_index() {
+        let messages = ["GET /health 200", "POST /login 500", "request took 500ms", "ok"];
+
+        assert_eq!(matches("LOGIN", &messages), Ok(vec![1]));
+        assert_eq!(matches("500", &messages), Ok(vec![1, 2]));
+        assert_eq!(matches("took 500", &messages), Ok(vec![2]));
+        assert_eq!(matches("login 200", &messages), Ok(vec![]));
+        assert_eq!(matches("", &messages), Ok(vec![]));
+    }
+
+    #[test]
+    fn rows_added_are_indexed() {
+        let mut all = rows(&["old error", "new error", "new ok"]);
+        let mut search = indexed(&all[..1]);
+        search.search("error", &all[..1]).unwrap();
+        assert_eq!(search.matches, vec![0]);
+
+        // The new rows arrive above the old one.
+        all.rotate_left(1);
+        search.rows_added(&[2], &[0, 1], &all);
+
+        assert_eq!(search.matches, vec![0, 2]);
+        search.search("/^new/", &all).unwrap();
+        assert_eq!(search.matches, vec![0, 1]);
+    }
+
+    #[test]
+    fn patterns_between_slashes_are_regexes() {
+        let messages = ["GET /health 200", "POST /login 500", "request took 500ms", "ok"];
+
+        assert_eq!(matches("/ 5\\d\\d$/", &messages), Ok(vec![1]));
+        assert_eq!(matches("/^post/", &messages), Ok(vec![1]));
+        assert_eq!(matches("//", &messages), Ok(vec![]));
+    }
+
+    #[test]
+    fn invalid_regexes_are_errors() {
+        let rows = rows(&["error("]);
+        let mut search = indexed(&rows);
+        search.search("error", &rows).unwrap();
+
+        let err = search.search("/error(/", &rows).unwrap_err();
+
+        assert!(err.starts_with("Invalid regex: "), "{}", err);
+        assert_eq!(search.pattern, "error");
+        assert_eq!(search.matches, vec![0]);
+    }
+
+    #[test]
+    fn ptr_is_not_searched() {
+        assert_eq!(matches("ptr", &["a", "b"]), Ok(vec![]));
+        assert_eq!(matches("/ptr-1/", &["a", "b"]), Ok(vec![]));
+    }
+
+    #[test]
+    fn next_and_previous_wrap_around() {
+        let search = ResultsSearch { matches: vec![1, 3], ..ResultsSearch::default() };
+
+        assert_eq!(search.next(0), Some(1));
+        assert_eq!(search.next(1), Some(3));
+        assert_eq!(search.next(3), Some(1));
+        assert_eq!(search.previous(3), Some(1));
+        assert_eq!(search.previous(1), Some(3));
+        assert_eq!(search.previous(0), Some(3));
+        assert_eq!(ResultsSearch::default().next(0), None);
+        assert_eq!(ResultsSearch::default().previous(0), None);
+    }
+}
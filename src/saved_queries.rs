@@ -0,0 +1,392 @@
+use std::{
+    fs, io,
+    io::Stdout,
+    path::{Path, PathBuf},
+    sync::mpsc::Sender,
+};
+
+use crate::{
+    cwl::AwsReq,
+    status_bar::{self, StatusMessage},
+    time_select::TimeSelector,
+    Mode, SelectedView, Widget,
+};
+use crossterm::event::KeyCode;
+use log::error;
+use serde::{Deserialize, Serialize};
+use tui::{
+    backend::CrosstermBackend,
+    layout::{Constraint, Direction, Layout},
+    style::{Color, Style},
+    text::{Span, Spans},
+    widgets::{Block, Borders, List, ListItem, Paragraph},
+    Frame,
+};
+use unicode_width::UnicodeWidthStr;
+
+const SAVED_QUERIES_FILE: &str = "saved_queries.json";
+
+#[derive(Clone, Serialize, Deserialize)]
+pub(crate) struct SavedQuery {
+    pub(crate) name: String,
+    pub(crate) query: String,
+    pub(crate) log_groups: Vec<String>,
+    pub(crate) start: String,
+    pub(crate) end: String,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum NameInput {
+    Save,
+    Rename,
+}
+
+#[derive(Default)]
+pub(crate) struct SavedQueries {
+    pub(crate) queries: Vec<SavedQuery>,
+    path: PathBuf,
+    selected: usize,
+    name_input: Option<NameInput>,
+    name: String,
+}
+
+impl SavedQueries {
+    /// A library without queries that is stored in `dir`.
+    pub(crate) fn empty(dir: &Path) -> Self {
+        Self { path: dir.join(SAVED_QUERIES_FILE), ..Self::default() }
+    }
+
+    /// Reads the saved queries from `dir`, a missing file means nothing has been saved yet.
+    pub(crate) fn load(dir: &Path) -> io::Result<Self> {
+        let path = dir.join(SAVED_QUERIES_FILE);
+        let queries = match fs::read_to_string(&path) {
+            Ok(content) => serde_json::from_str(&content)?,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => vec![],
+            Err(err) => return Err(err),
+        };
+        Ok(Self { queries, path, ..Self::default() })
+    }
+
+    /// Adds `saved`, replacing the query with the same name, and stores the library.
+    fn save(&mut self, saved: SavedQuery) -> io::Result<()> {
+        match self.queries.iter().position(|q| q.name == saved.name) {
+            Some(i) => self.queries[i] = saved,
+            None => self.queries.push(saved),
+        }
+        self.store()
+    }
+
+    /// Removes the query at `index` and stores the library.
+    fn delete(&mut self, index: usize) -> io::Result<Option<SavedQuery>> {
+        if index >= self.queries.len() {
+            return Ok(None);
+        }
+        let removed = self.queries.remove(index);
+        self.selected = index.saturating_sub(1);
+        self.store().map(|_| Some(removed))
+    }
+
+    /// Writes the library to a temporary file first and renames it over the library, so a crash
+    /// while writing leaves the previous library intact.
+    fn store(&self) -> io::Result<()> {
+        let tmp = self.path.with_extension("json.tmp");
+        fs::write(&tmp, serde_json::to_string_pretty(&self.queries)?)?;
+        fs::rename(&tmp, &self.path)
+    }
+}
+
+pub(crate) fn draw(
+    app: std::sync::MutexGuard<crate::App>,
+    frame: &mut Frame<CrosstermBackend<Stdout>>,
+) {
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(2)
+        .constraints(
+            [
+                Constraint::Length(3),
+                Constraint::Min(1),
+                Constraint::Length(9),
+                Constraint::Length(3),
+                Constraint::Length(2),
+            ]
+            .as_ref(),
+        )
+        .split(frame.size());
+
+    let saved = &app.saved_queries;
+    let title = match saved.name_input {
+        Some(NameInput::Save) => "save current query as",
+        Some(NameInput::Rename) => "rename to",
+        None => "name",
+    };
+    let input = Paragraph::new(saved.name.as_str())
+        .style(match saved.name_input {
+            Some(_) => Style::default().fg(Color::Yellow),
+            None => Style::default(),
+        })
+        .block(Block::default().borders(Borders::ALL).title(title));
+    frame.render_widget(input, chunks[0]);
+    if saved.name_input.is_some() {
+        frame.set_cursor(chunks[0].x + saved.name.width() as u16 + 1, chunks[0].y + 1)
+    }
+
+    let items: Vec<ListItem> = saved
+        .queries
+        .iter()
+        .enumerate()
+        .map(|(i, q)| {
+            let content = vec![Spans::from(Span::raw(format!(
+                "{}: {} [{}] {} - {}",
+                i,
+                q.name,
+                q.log_groups.join(", "),
+                q.start,
+                q.end
+            )))];
+            ListItem::new(content).style(if i == saved.selected {
+                Style::default().fg(Color::Red)
+            } else {
+                Style::default()
+            })
+        })
+        .collect();
+    let list = List::new(items).block(
+        Block::default()
+            .style(match saved.name_input {
+                None => Style::default().fg(Color::Yellow),
+                Some(_) => Style::default(),
+            })
+            .borders(Borders::ALL)
+            .title("saved queries"),
+    );
+    frame.render_widget(list, chunks[1]);
+
+    let query = saved.queries.get(saved.selected).map(|q| q.query.as_str()).unwrap_or("");
+    let query = Paragraph::new(query).block(Block::default().borders(Borders::ALL).title("query"));
+    frame.render_widget(query, chunks[2]);
+
+    status_bar::draw(app, frame, chunks[3]);
+
+    let controls = Paragraph::new(
+        "Enter (load) | a (save current query) | R (rename) | d (delete) | Escape (go back)",
+    )
+    .block(Block::default().borders(Borders::NONE).title("controls"));
+    frame.render_widget(controls, chunks[4]);
+}
+
+pub(crate) fn handle_input(
+    mut app: std::sync::MutexGuard<crate::App>,
+    key_code: KeyCode,
+    _cwl: &Sender<AwsReq>,
+) {
+    match app.saved_queries.name_input {
+        Some(name_input) => match key_code {
+            KeyCode::Esc => {
+                app.saved_queries.name_input = None;
+                app.mode = Mode::Normal;
+            }
+            KeyCode::Enter => {
+                let name = app.saved_queries.name.trim().to_string();
+                if name.is_empty() {
+                    app.status_message = StatusMessage::error("Name can't be empty");
+                    return;
+                }
+                match name_input {
+                    NameInput::Save => save_current(&mut app, name),
+                    NameInput::Rename => rename_selected(&mut app, name),
+                }
+                app.saved_queries.name_input = None;
+                app.mode = Mode::Normal;
+            }
+            KeyCode::Backspace => {
+                app.saved_queries.name.pop();
+            }
+            KeyCode::Char(c) => app.saved_queries.name.push(c),
+            _ => {}
+        },
+        None => match key_code {
+            KeyCode::Esc => {
+                app.selected = SelectedView::Overview;
+                app.focused = Widget::Query;
+            }
+            KeyCode::Char('j') | KeyCode::Down => {
+                let len = app.saved_queries.queries.len();
+                app.saved_queries.selected =
+                    if len > 0 { (app.saved_queries.selected + 1) % len } else { 0 };
+            }
+            KeyCode::Char('k') | KeyCode::Up => {
+                let l = app.saved_queries.queries.len();
+                let r = app.saved_queries.selected;
+                app.saved_queries.selected = if r > 0 && l > 0 {
+                    (r - 1) % l
+                } else if r == 0 && l > 0 {
+                    l - 1
+                } else {
+                    0
+                };
+            }
+            KeyCode::Enter => load_selected(&mut app),
+            KeyCode::Char('a') => {
+                app.saved_queries.name = String::new();
+                app.saved_queries.name_input = Some(NameInput::Save);
+                app.mode = Mode::Insert;
+            }
+            KeyCode::Char('R') => {
+                if let Some(q) = app.saved_queries.queries.get(app.saved_queries.selected) {
+                    app.saved_queries.name = q.name.clone();
+                    app.saved_queries.name_input = Some(NameInput::Rename);
+                    app.mode = Mode::Insert;
+                }
+            }
+            KeyCode::Char('d') => {
+                let selected = app.saved_queries.selected;
+                match app.saved_queries.delete(selected) {
+                    Ok(Some(removed)) => {
+                        app.status_message =
+                            StatusMessage::info(&format!("Deleted saved query '{}'", removed.name))
+                    }
+                    Ok(None) => {}
+                    Err(err) => store_failed(&mut app, err),
+                }
+            }
+            _ => {}
+        },
+    }
+}
+
+fn load_selected(app: &mut std::sync::MutexGuard<crate::App>) {
+    let saved = match app.saved_queries.queries.get(app.saved_queries.selected) {
+        Some(saved) => saved.clone(),
+        None => return,
+    };
+    match TimeSelector::from_strings(&saved.start, &saved.end) {
+        Ok(time_selector) => {
+            app.time_selector = time_selector;
+            app.query = saved.query;
+            app.log_groups.selected_log_groups = saved.log_groups;
+            app.selected = SelectedView::Overview;
+            app.focused = Widget::Query;
+            app.status_message = StatusMessage::info(&format!("Loaded query '{}'", saved.name));
+        }
+        Err(err) => app.status_message = StatusMessage::error(err),
+    }
+}
+
+fn save_current(app: &mut std::sync::MutexGuard<crate::App>, name: String) {
+    let saved = SavedQuery {
+        name: name.clone(),
+        query: app.query.clone(),
+        log_groups: app.log_groups.selected_log_groups.clone(),
+        start: app.time_selector.selected_start_string.clone(),
+        end: app.time_selector.selected_end_string.clone(),
+    };
+    match app.saved_queries.save(saved) {
+        Ok(()) => app.status_message = StatusMessage::info(&format!("Saved query '{}'", name)),
+        Err(err) => store_failed(app, err),
+    }
+}
+
+fn rename_selected(app: &mut std::sync::MutexGuard<crate::App>, name: String) {
+    if app.saved_queries.queries.iter().any(|q| q.name == name) {
+        app.status_message =
+            StatusMessage::error(&format!("A saved query named '{}' already exists", name));
+        return;
+    }
+    let selected = app.saved_queries.selected;
+    if let Some(q) = app.saved_queries.queries.get_mut(selected) {
+        q.name = name.clone();
+        match app.saved_queries.store() {
+            Ok(()) => {
+                app.status_message = StatusMessage::info(&format!("Renamed query to '{}'", name))
+            }
+            Err(err) => store_failed(app, err),
+        }
+    }
+}
+
+fn store_failed(app: &mut std::sync::MutexGuard<crate::App>, err: io::Error) {
+    error!("Storing saved queries failed: {:?}", err);
+    app.status_message = StatusMessage::error("Storing saved queries failed");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn query(name: &str, query: &str) -> SavedQuery {
+        SavedQuery {
+            name: name.to_string(),
+            query: query.to_string(),
+            log_groups: vec!["/a".to_string()],
+            start: "1h".to_string(),
+            end: "now".to_string(),
+        }
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("rcwi-{}-{}", name, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn names(saved: &SavedQueries) -> Vec<&str> {
+        saved.queries.iter().map(|q| q.name.as_str()).collect()
+    }
+
+    #[test]
+    fn missing_file_means_no_saved_queries() {
+        let dir = temp_dir("saved-missing");
+
+        let saved = SavedQueries::load(&dir).unwrap();
+
+        assert!(saved.queries.is_empty());
+        assert_eq!(saved.path, dir.join(SAVED_QUERIES_FILE));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn saved_queries_are_loaded_back() {
+        let dir = temp_dir("saved-load");
+        let mut saved = SavedQueries::load(&dir).unwrap();
+
+        saved.save(query("errors", "filter @message like /error/")).unwrap();
+        saved.save(query("slow", "filter @duration > 1000")).unwrap();
+        saved.save(query("errors", "filter level = 'error'")).unwrap();
+
+        let loaded = SavedQueries::load(&dir).unwrap();
+        assert_eq!(names(&loaded), vec!["errors", "slow"]);
+        assert_eq!(loaded.queries[0].query, "filter level = 'error'");
+        assert!(!dir.join("saved_queries.json.tmp").exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn deleted_queries_are_gone_after_loading() {
+        let dir = temp_dir("saved-delete");
+        let mut saved = SavedQueries::load(&dir).unwrap();
+        saved.save(query("a", "fields @message")).unwrap();
+        saved.save(query("b", "fields @timestamp")).unwrap();
+        saved.selected = 1;
+
+        assert_eq!(saved.delete(1).unwrap().map(|q| q.name), Some("b".to_string()));
+        assert!(saved.delete(5).unwrap().is_none());
+
+        assert_eq!(saved.selected, 0);
+        assert_eq!(names(&SavedQueries::load(&dir).unwrap()), vec!["a"]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn an_empty_library_still_stores_to_its_file() {
+        let dir = temp_dir("saved-corrupt");
+        fs::write(dir.join(SAVED_QUERIES_FILE), "[{\"name\": ").unwrap();
+        assert!(SavedQueries::load(&dir).is_err());
+
+        let mut saved = SavedQueries::empty(&dir);
+        saved.save(query("a", "fields @message")).unwrap();
+
+        assert_eq!(names(&SavedQueries::load(&dir).unwrap()), vec!["a"]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
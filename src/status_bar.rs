@@ -4,13 +4,17 @@ use tui::{
     backend::CrosstermBackend,
     layout::Rect,
     style::{Color, Style},
-    widgets::{Block, Borders, Paragraph},
+    widgets::{Block, Borders, Clear, Paragraph, Wrap},
     Frame,
 };
 
+use crate::{cwl::error::BackendError, overview::centered_rect, tabs};
+
 pub(crate) struct StatusMessage {
     text: String,
     level: StatusLevel,
+    /// Shown in a popup with `!`, for errors that have more to tell than fits in the bar.
+    detail: Option<String>,
 }
 
 pub(crate) enum StatusLevel {
@@ -29,16 +33,31 @@ impl Display for StatusLevel {
 
 impl Default for StatusMessage {
     fn default() -> Self {
-        Self { text: "".to_string(), level: StatusLevel::Info }
+        Self { text: "".to_string(), level: StatusLevel::Info, detail: None }
     }
 }
 
 impl StatusMessage {
     pub(crate) fn info(text: &str) -> Self {
-        Self { text: text.to_string(), level: StatusLevel::Info }
+        Self { text: text.to_string(), level: StatusLevel::Info, detail: None }
     }
     pub(crate) fn error(text: &str) -> Self {
-        Self { text: text.to_string(), level: StatusLevel::Error }
+        Self { text: text.to_string(), level: StatusLevel::Error, detail: None }
+    }
+    /// An error of a request to AWS, `context` says what failed and the summary of `err` why.
+    pub(crate) fn aws_error(context: &str, err: &BackendError) -> Self {
+        Self {
+            text: format!("{}: {}", context, err.summary()),
+            level: StatusLevel::Error,
+            detail: Some(err.detail()),
+        }
+    }
+    pub(crate) fn has_detail(&self) -> bool {
+        self.detail.is_some()
+    }
+    #[cfg(test)]
+    pub(crate) fn text(&self) -> &str {
+        &self.text
     }
 }
 
@@ -47,12 +66,33 @@ pub(crate) fn draw(
     frame: &mut Frame<CrosstermBackend<io::Stdout>>,
     area: Rect,
 ) {
-    let status_str = format!("[{}] {}", app.status_message.level, app.status_message.text);
+    let mut status_str = format!("[{}] {}", app.status_message.level, app.status_message.text);
+    if app.status_message.has_detail() {
+        status_str += " (! for details)";
+    }
     let status_bar = Paragraph::new(status_str.as_str())
         .style(match app.status_message.level {
             StatusLevel::Error => Style::default().fg(Color::Red),
             _ => Style::default(),
         })
-        .block(Block::default().borders(Borders::ALL).title("status"));
+        .block(Block::default().borders(Borders::ALL).title(tabs::status_title(&app)));
     frame.render_widget(status_bar, area);
 }
+
+/// Draws the detail of the status message in a popup over the current view when it's open.
+pub(crate) fn draw_detail(
+    app: MutexGuard<crate::App>,
+    frame: &mut Frame<CrosstermBackend<io::Stdout>>,
+) {
+    let detail = match (&app.status_message.detail, app.status_detail_open) {
+        (Some(detail), true) => detail,
+        _ => return,
+    };
+    let area = centered_rect(70, 40, frame.size());
+    let popup = Paragraph::new(format!("{}\n\n{}", app.status_message.text, detail))
+        .style(Style::default().fg(Color::Red))
+        .wrap(Wrap { trim: false })
+        .block(Block::default().borders(Borders::ALL).title("error details (any key to close)"));
+    frame.render_widget(Clear, area);
+    frame.render_widget(popup, area);
+}
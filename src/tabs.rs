@@ -0,0 +1,178 @@
+use std::sync::mpsc::Sender;
+
+use crate::{
+    cwl::AwsReq,
+    overview::LogResults,
+    status_bar::StatusMessage,
+    time_select::{TimeSelector, TimeSelectorInput},
+    App,
+};
+use tui::{
+    style::{Color, Style},
+    text::{Span, Spans},
+};
+
+/// Query tabs, each with its own query, log groups, time range and results.
+///
+/// The active tab lives in the fields of [`App`] the views already use, the other tabs are
+/// parked here until they are switched to. Tabs are addressed by id in requests, so results
+/// of a query end up in the tab it was started in even when another tab is active by then.
+pub(crate) struct Tabs {
+    /// All tabs in order, the entry of the active tab is out of date.
+    tabs: Vec<QueryTab>,
+    active: usize,
+    next_id: usize,
+}
+
+#[derive(Default)]
+pub(crate) struct QueryTab {
+    id: usize,
+    query: String,
+    log_groups: Vec<String>,
+    time_selector: TimeSelector,
+    log_results: LogResults,
+}
+
+impl Default for Tabs {
+    fn default() -> Self {
+        Self { tabs: vec![QueryTab::default()], active: 0, next_id: 1 }
+    }
+}
+
+impl Tabs {
+    /// Id of the active tab.
+    pub(crate) fn active_id(&self) -> usize {
+        self.tabs[self.active].id
+    }
+
+    pub(crate) fn active_index(&self) -> usize {
+        self.active
+    }
+
+    fn parked(&self, id: usize) -> Option<&QueryTab> {
+        self.tabs.iter().enumerate().find(|(i, t)| t.id == id && *i != self.active).map(|(_, t)| t)
+    }
+
+    fn parked_mut(&mut self, id: usize) -> Option<&mut QueryTab> {
+        let active = self.active;
+        self.tabs.iter_mut().enumerate().find(|(i, t)| t.id == id && *i != active).map(|(_, t)| t)
+    }
+}
+
+/// Results of the tab with `id`, `None` once the tab is closed.
+pub(crate) fn results_mut(app: &mut App, id: usize) -> Option<&mut LogResults> {
+    if app.tabs.active_id() == id {
+        Some(&mut app.log_results)
+    } else {
+        app.tabs.parked_mut(id).map(|tab| &mut tab.log_results)
+    }
+}
+
+/// Query, log groups and time range (as timestamps) of the tab with `id`.
+pub(crate) fn query_of(app: &App, id: usize) -> Option<(String, Vec<String>, (i64, i64))> {
+    if app.tabs.active_id() == id {
+        let time_range = app.time_selector.to_timestamps();
+        Some((app.query.clone(), app.log_groups.selected_log_groups.clone(), time_range))
+    } else {
+        let tab = app.tabs.parked(id)?;
+        Some((tab.query.clone(), tab.log_groups.clone(), tab.time_selector.to_timestamps()))
+    }
+}
+
+/// Moves the active tab from the fields of `app` into its entry.
+fn park(app: &mut App) {
+    let tab = &mut app.tabs.tabs[app.tabs.active];
+    tab.query = std::mem::take(&mut app.query);
+    tab.log_groups = std::mem::take(&mut app.log_groups.selected_log_groups);
+    tab.time_selector = std::mem::take(&mut app.time_selector);
+    tab.log_results = std::mem::take(&mut app.log_results);
+}
+
+fn unpark(app: &mut App, index: usize) {
+    let tab = &mut app.tabs.tabs[index];
+    app.query = std::mem::take(&mut tab.query);
+    app.log_groups.selected_log_groups = std::mem::take(&mut tab.log_groups);
+    app.time_selector = std::mem::take(&mut tab.time_selector);
+    app.log_results = std::mem::take(&mut tab.log_results);
+    app.tabs.active = index;
+}
+
+/// Switches to the tab at `index`, wrapping around at either end.
+pub(crate) fn switch(app: &mut App, index: isize, cwl: &Sender<AwsReq>) {
+    let index = index.rem_euclid(app.tabs.tabs.len() as isize) as usize;
+    if index == app.tabs.active {
+        return;
+    }
+    park(app);
+    unpark(app, index);
+    cwl.send(AwsReq::GetLogGroupFields).unwrap();
+}
+
+/// Opens a tab after the active one, starting from the query, log groups and time range of the
+/// active tab so they can be tweaked and compared.
+pub(crate) fn open(app: &mut App) {
+    let mut time_selector = app.time_selector.clone();
+    time_selector.popup = false;
+    time_selector.input = TimeSelectorInput::Start;
+    let tab = QueryTab {
+        id: app.tabs.next_id,
+        query: app.query.clone(),
+        log_groups: app.log_groups.selected_log_groups.clone(),
+        time_selector,
+        log_results: LogResults::default(),
+    };
+    app.tabs.next_id += 1;
+    let index = app.tabs.active + 1;
+    park(app);
+    app.tabs.tabs.insert(index, tab);
+    unpark(app, index);
+    app.status_message = StatusMessage::info(&format!("Opened tab {}", index + 1));
+}
+
+/// Closes the active tab, cancelling its query. The last tab can't be closed.
+pub(crate) fn close(app: &mut App, cwl: &Sender<AwsReq>) {
+    if app.tabs.tabs.len() == 1 {
+        app.status_message = StatusMessage::error("The last tab can't be closed");
+        return;
+    }
+    // The worker stops the query once the tab is gone.
+    let index = app.tabs.active;
+    app.tabs.tabs.remove(index);
+    let next = index.min(app.tabs.tabs.len() - 1);
+    unpark(app, next);
+    cwl.send(AwsReq::GetLogGroupFields).unwrap();
+    app.status_message = StatusMessage::info(&format!("Closed tab {}", index + 1));
+}
+
+/// The tab number with the state of its latest query, like `2 Running 3.1s`.
+fn title(index: usize, log_results: &LogResults) -> String {
+    match &log_results.progress {
+        None => format!("{} new", index + 1),
+        Some(progress) if progress.finished.is_none() => format!(
+            "{} {} {:.1}s",
+            index + 1,
+            progress.status,
+            progress.started.elapsed().as_secs_f64()
+        ),
+        Some(progress) => {
+            format!("{} {} {} rows", index + 1, progress.status, log_results.query_results.len())
+        }
+    }
+}
+
+/// Title of the status bar, with the state of every tab once there is more than one.
+pub(crate) fn status_title(app: &App) -> Spans<'static> {
+    let mut spans = vec![Span::raw("status")];
+    if app.tabs.tabs.len() == 1 {
+        return Spans::from(spans);
+    }
+    for (i, tab) in app.tabs.tabs.iter().enumerate() {
+        spans.push(Span::raw(" | "));
+        if i == app.tabs.active {
+            spans.push(Span::styled(title(i, &app.log_results), Style::default().fg(Color::Cyan)));
+        } else {
+            spans.push(Span::raw(title(i, &tab.log_results)));
+        }
+    }
+    Spans::from(spans)
+}
@@ -0,0 +1,231 @@
+use std::{collections::VecDeque, io::Stdout, sync::mpsc::Sender};
+
+use crate::{
+    cwl::{backend::LogEvent, AwsReq},
+    overview::{draw_rows, yank_row, ResultRow},
+    status_bar::{self, StatusMessage},
+    Mode, SelectedView, Widget,
+};
+use crossterm::event::KeyCode;
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+use tui::{
+    backend::CrosstermBackend,
+    layout::{Constraint, Direction, Layout},
+    style::{Color, Style},
+    widgets::{Block, Borders, Paragraph},
+    Frame,
+};
+use unicode_width::UnicodeWidthStr;
+
+/// The oldest events are dropped once the buffer holds this many.
+const MAX_EVENTS: usize = 10_000;
+
+/// Events of the selected log groups as they come in, see [`crate::cwl`] for the polling.
+#[derive(Default)]
+pub(crate) struct Tail {
+    pub(crate) events: VecDeque<ResultRow>,
+    pub(crate) log_groups: Vec<String>,
+    columns: Vec<String>,
+    pub(crate) filter: String,
+    editing_filter: bool,
+    /// Cleared to stop the worker polling.
+    pub(crate) running: bool,
+    pub(crate) paused: bool,
+    /// Set to make the worker start over, for example with a new filter.
+    pub(crate) restart: bool,
+    /// Keeps the newest event selected as events come in.
+    follow: bool,
+    selected: usize,
+}
+
+impl Tail {
+    /// Appends `rows`, dropping the oldest events when the buffer is full.
+    pub(crate) fn push(&mut self, rows: impl Iterator<Item = ResultRow>) {
+        self.events.extend(rows);
+        let overflow = self.events.len().saturating_sub(MAX_EVENTS);
+        self.events.drain(..overflow);
+        self.selected = if self.follow {
+            self.events.len().saturating_sub(1)
+        } else {
+            self.selected.saturating_sub(overflow)
+        };
+    }
+}
+
+pub(crate) fn to_row(log_group: &str, event: LogEvent) -> ResultRow {
+    let timestamp = OffsetDateTime::from_unix_timestamp_nanos(event.timestamp as i128 * 1_000_000)
+        .ok()
+        .and_then(|dt| dt.format(&Rfc3339).ok())
+        .unwrap_or_else(|| event.timestamp.to_string());
+    ResultRow {
+        fields: vec![
+            ("@timestamp".to_string(), timestamp),
+            ("@logGroup".to_string(), log_group.to_string()),
+            ("@logStream".to_string(), event.log_stream_name),
+            ("@message".to_string(), event.message),
+        ],
+    }
+}
+
+/// Starts tailing the selected log groups and switches to the tail view.
+pub(crate) fn open(app: &mut std::sync::MutexGuard<crate::App>, cwl: &Sender<AwsReq>) {
+    if app.log_groups.selected_log_groups.is_empty() {
+        app.status_message = StatusMessage::error("Select log groups to tail");
+        return;
+    }
+    let log_groups = app.log_groups.selected_log_groups.clone();
+    let mut columns = vec!["@timestamp".to_string()];
+    if log_groups.len() > 1 {
+        columns.push("@logGroup".to_string());
+    }
+    columns.extend(["@logStream".to_string(), "@message".to_string()]);
+    app.tail = Tail {
+        log_groups,
+        columns,
+        filter: std::mem::take(&mut app.tail.filter),
+        running: true,
+        restart: true,
+        follow: true,
+        ..Tail::default()
+    };
+    app.selected = SelectedView::Tail;
+    cwl.send(AwsReq::Tail).unwrap();
+}
+
+pub(crate) fn draw(
+    mut app: std::sync::MutexGuard<crate::App>,
+    frame: &mut Frame<CrosstermBackend<Stdout>>,
+) {
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(2)
+        .constraints(
+            [
+                Constraint::Length(3),
+                Constraint::Min(1),
+                Constraint::Length(3),
+                Constraint::Length(2),
+            ]
+            .as_ref(),
+        )
+        .split(frame.size());
+
+    let tail = &app.tail;
+    let filter = Paragraph::new(tail.filter.as_str())
+        .style(if tail.editing_filter {
+            Style::default().fg(Color::Yellow)
+        } else {
+            Style::default()
+        })
+        .block(Block::default().borders(Borders::ALL).title("filter pattern"));
+    frame.render_widget(filter, chunks[0]);
+    if tail.editing_filter {
+        frame.set_cursor(chunks[0].x + tail.filter.width() as u16 + 1, chunks[0].y + 1)
+    }
+
+    let state = match (tail.running, tail.paused, tail.follow) {
+        (false, _, _) => "stopped",
+        (true, true, _) => "paused",
+        (true, false, true) => "following",
+        (true, false, false) => "scrolling",
+    };
+    let title =
+        format!("tail {} ({}, {} events)", tail.log_groups.join(", "), state, tail.events.len());
+    let block = Block::default()
+        .style(if tail.editing_filter {
+            Style::default()
+        } else {
+            Style::default().fg(Color::Yellow)
+        })
+        .borders(Borders::ALL)
+        .title(title);
+    let (selected, columns) = (tail.selected, tail.columns.clone());
+    let rows = app.tail.events.make_contiguous();
+    draw_rows(frame, chunks[1], block, rows, &columns, selected, Some(&[]));
+
+    status_bar::draw(app, frame, chunks[2]);
+
+    let controls = Paragraph::new(
+        "p (pause/resume) | f (filter) | G (follow) | jk (scroll) | y (yank) | r (restart) | Escape (stop and go back)",
+    )
+    .block(Block::default().borders(Borders::NONE).title("controls"));
+    frame.render_widget(controls, chunks[3]);
+}
+
+pub(crate) fn handle_input(
+    mut app: std::sync::MutexGuard<crate::App>,
+    key_code: KeyCode,
+    cwl: &Sender<AwsReq>,
+) {
+    if app.tail.editing_filter {
+        match key_code {
+            KeyCode::Esc => {
+                app.tail.editing_filter = false;
+                app.mode = Mode::Normal;
+            }
+            KeyCode::Enter => {
+                app.tail.editing_filter = false;
+                app.mode = Mode::Normal;
+                restart(app, cwl);
+            }
+            KeyCode::Backspace => {
+                app.tail.filter.pop();
+            }
+            KeyCode::Char(c) => app.tail.filter.push(c),
+            _ => {}
+        }
+        return;
+    }
+    match key_code {
+        KeyCode::Esc => {
+            app.tail.running = false;
+            app.selected = SelectedView::Overview;
+            app.focused = Widget::LogGroups;
+        }
+        KeyCode::Char('p') | KeyCode::Char(' ') => app.tail.paused = !app.tail.paused,
+        KeyCode::Char('f') => {
+            app.tail.editing_filter = true;
+            app.mode = Mode::Insert;
+        }
+        KeyCode::Char('r') => restart(app, cwl),
+        KeyCode::Char('G') | KeyCode::End => {
+            app.tail.follow = true;
+            app.tail.selected = app.tail.events.len().saturating_sub(1);
+        }
+        KeyCode::Char('j') | KeyCode::Down => {
+            let len = app.tail.events.len();
+            app.tail.selected = if len > 0 { (app.tail.selected + 1) % len } else { 0 };
+            app.tail.follow = false;
+        }
+        KeyCode::Char('k') | KeyCode::Up => {
+            let l = app.tail.events.len();
+            let r = app.tail.selected;
+            app.tail.selected = if r > 0 && l > 0 {
+                (r - 1) % l
+            } else if r == 0 && l > 0 {
+                l - 1
+            } else {
+                0
+            };
+            app.tail.follow = false;
+        }
+        KeyCode::Char('y') => {
+            if let Some(row) = app.tail.events.get(app.tail.selected) {
+                app.status_message = yank_row(row, &app.tail.columns);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Clears the buffer and starts over with the current filter, restarting the worker when the
+/// tail was stopped by an error.
+fn restart(mut app: std::sync::MutexGuard<crate::App>, cwl: &Sender<AwsReq>) {
+    app.tail.restart = true;
+    app.tail.paused = false;
+    app.tail.follow = true;
+    if !app.tail.running {
+        app.tail.running = true;
+        cwl.send(AwsReq::Tail).unwrap();
+    }
+}
@@ -7,7 +7,7 @@ use std::{
 
 use time::{
     format_description::{self, well_known::Rfc3339},
-    Date, OffsetDateTime, PrimitiveDateTime,
+    Date, OffsetDateTime, PrimitiveDateTime, UtcOffset,
 };
 use tui::{
     backend::CrosstermBackend,
@@ -19,6 +19,7 @@ use tui::{
 
 use crate::Widget;
 
+#[derive(Clone)]
 pub(crate) struct TimeSelector {
     selected_start: Time,
     selected_end: Time,
@@ -26,6 +27,8 @@ pub(crate) struct TimeSelector {
     pub(crate) selected_start_string: String,
     pub(crate) selected_end_string: String,
     pub(crate) input: TimeSelectorInput,
+    /// The offset of a time given with one, like `2022-01-01T10:00:00+02:00`.
+    offset: Option<UtcOffset>,
 }
 impl TimeSelector {
     pub(crate) fn from_strings(start: &str, end: &str) -> Result<Self, &'static str> {
@@ -41,9 +44,48 @@ impl TimeSelector {
             selected_start_string: start.to_string(),
             selected_end_string: end.to_string(),
             input: TimeSelectorInput::Start,
+            offset: explicit_offset(start).or_else(|| explicit_offset(end)),
         })
     }
 
+    /// A time range between two fixed unix timestamps.
+    pub(crate) fn from_timestamps(start: i64, end: i64) -> Result<Self, &'static str> {
+        let start = OffsetDateTime::from_unix_timestamp(start).map_err(|_| "Invalid start time")?;
+        let end = OffsetDateTime::from_unix_timestamp(end).map_err(|_| "Invalid end time")?;
+        Ok(Self::new(Time::Specific(start), Time::Specific(end)))
+    }
+
+    /// A time range covering the last `seconds` seconds up until now.
+    pub(crate) fn last(seconds: i64) -> Self {
+        let (unit, value) = [
+            (RelativeUnit::Days, 3600 * 24),
+            (RelativeUnit::Hours, 3600),
+            (RelativeUnit::Minutes, 60),
+        ]
+        .iter()
+        .find(|(_, multiplier)| seconds % multiplier == 0)
+        .map(|(unit, multiplier)| (*unit, seconds / multiplier))
+        .unwrap_or((RelativeUnit::Seconds, seconds));
+        Self::new(Time::Relative(unit, value.max(0) as u32), Time::Now)
+    }
+
+    fn new(start: Time, end: Time) -> Self {
+        Self {
+            selected_start_string: start.to_string(),
+            selected_end_string: end.to_string(),
+            selected_start: start,
+            selected_end: end,
+            popup: false,
+            input: TimeSelectorInput::Start,
+            offset: None,
+        }
+    }
+
+    /// The offset the time range was given in, `None` for relative times and timestamps.
+    pub(crate) fn utc_offset(&self) -> Option<UtcOffset> {
+        self.offset
+    }
+
     pub(crate) fn to_timestamps(&self) -> (i64, i64) {
         if let Time::Relative(u, v) = self.selected_start {
             let end = match self.selected_end {
@@ -87,20 +129,11 @@ impl Display for TimeSelector {
 
 impl Default for TimeSelector {
     fn default() -> Self {
-        let default_start = Time::Relative(RelativeUnit::Hours, 1);
-        let default_end = Time::Now;
-        Self {
-            selected_start_string: default_start.to_string(),
-            selected_end_string: default_end.to_string(),
-            selected_start: default_start,
-            selected_end: default_end,
-            popup: false,
-            input: TimeSelectorInput::Start,
-        }
+        Self::new(Time::Relative(RelativeUnit::Hours, 1), Time::Now)
     }
 }
 
-fn now() -> i64 {
+pub(crate) fn now() -> i64 {
     let now = SystemTime::now();
     now.duration_since(UNIX_EPOCH).unwrap().as_secs() as i64
 }
@@ -196,6 +229,10 @@ fn to_time(string: &str) -> Result<Time, &'static str> {
     Err("Something wrong")
 }
 
+fn explicit_offset(string: &str) -> Option<UtcOffset> {
+    OffsetDateTime::parse(string.trim(), &Rfc3339).ok().map(|dt| dt.offset())
+}
+
 fn to_offset(unit: RelativeUnit, value: u32) -> i64 {
     let multiplier = match unit {
         RelativeUnit::Seconds => 1,
@@ -230,6 +267,7 @@ impl Display for RelativeUnit {
         }
     }
 }
+#[derive(Clone, Copy)]
 enum Time {
     Relative(RelativeUnit, u32),
     Specific(OffsetDateTime),
@@ -270,3 +308,26 @@ pub(crate) fn draw(
         .block(Block::default().borders(Borders::ALL).title("selected time"));
     frame.render_widget(status_bar, area);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn offset_is_taken_from_times_given_with_one() {
+        let offset = |start, end| TimeSelector::from_strings(start, end).unwrap().utc_offset();
+
+        assert_eq!(
+            offset("2022-01-01T10:00:00+02:00", "now"),
+            Some(UtcOffset::from_hms(2, 0, 0).unwrap())
+        );
+        assert_eq!(
+            offset("1h", "2022-01-01T10:00:00-05:30"),
+            Some(UtcOffset::from_hms(-5, -30, 0).unwrap())
+        );
+        assert_eq!(offset("2022-01-01T10:00:00Z", "now"), Some(UtcOffset::UTC));
+        assert_eq!(offset("2022-01-01 10:00:00", "2022-01-02"), None);
+        assert_eq!(offset("1h", "now"), None);
+        assert_eq!(TimeSelector::from_timestamps(0, 60).unwrap().utc_offset(), None);
+    }
+}